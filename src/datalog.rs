@@ -29,6 +29,48 @@ crepe::crepe! {
     struct CoCitation(usize, usize, usize);
 
     CoCitation(witness, a, b) <- Link(witness, a), Link(witness, b), (a < b);
+
+    // reachability
+
+    @input
+    struct Page(usize);
+
+    @input
+    struct Root(usize);
+
+    struct Reachable(usize);
+
+    Reachable(root) <- Root(root);
+    Reachable(target) <- Reachable(source), Link(source, target);
+
+    @output
+    struct Unreachable(usize);
+
+    Unreachable(page) <- Page(page), !Reachable(page);
+
+    // maintenance
+
+    struct Linked(usize);
+
+    Linked(target) <- Link(_, target);
+
+    struct Indexed(usize);
+
+    Indexed(child) <- Parent(parent, child), Page(parent);
+
+    struct LinksOut(usize);
+
+    LinksOut(source) <- Link(source, _);
+
+    @output
+    struct Orphan(usize);
+
+    Orphan(page) <- Page(page), !Root(page), !Linked(page), !Indexed(page);
+
+    @output
+    struct DeadEnd(usize);
+
+    DeadEnd(page) <- Page(page), !LinksOut(page);
 }
 
 pub struct Datalog {
@@ -67,6 +109,19 @@ impl Datalog {
         self.runtime.link.push(Link(source_id, target_id));
     }
 
+    /// Mark a key as an actual page, as opposed to a directory or an outside
+    /// source of links.
+    pub fn add_page(&mut self, page: &str) {
+        let id = self.intern(page);
+        self.runtime.page.push(Page(id));
+    }
+
+    /// Mark the page from which reachability is measured.
+    pub fn add_root(&mut self, root: &str) {
+        let id = self.intern(root);
+        self.runtime.root.push(Root(id));
+    }
+
     pub fn add_parent(&mut self, parent: &str, child: &str) {
         let p_id = self.intern(parent);
         let c_id = self.intern(child);
@@ -82,7 +137,8 @@ impl Datalog {
             map
         };
 
-        let (backlinks, children, co_citations) = self.runtime.run();
+        let (backlinks, children, co_citations, unreachable, orphans, dead_ends) =
+            self.runtime.run();
 
         Solution {
             lookup: self.interner,
@@ -103,6 +159,9 @@ impl Datalog {
                 map
             },
             parents,
+            unreachable: unreachable.into_iter().map(|Unreachable(id)| id).collect(),
+            orphans: orphans.into_iter().map(|Orphan(id)| id).collect(),
+            dead_ends: dead_ends.into_iter().map(|DeadEnd(id)| id).collect(),
             co_citations: {
                 let mut map = HashMap::new();
                 for CoCitation(_, a, b) in co_citations {
@@ -129,13 +188,17 @@ pub struct Solution {
     // Stores ID -> List<ID>
     backlinks: HashMap<usize, Vec<usize>>,
     // Map child_id -> parent_id
-    #[allow(dead_code)]
     parents: HashMap<usize, usize>,
     // Map parent_id -> Vec<child_id>
     children: HashMap<usize, Vec<usize>>,
     // Map (parent_id, child_id) -> count
-    #[allow(dead_code)]
     co_citations: HashMap<usize, HashMap<usize, usize>>,
+    // Pages not reachable by links from the root
+    unreachable: Vec<usize>,
+    // Pages with no inbound links and no parent page
+    orphans: Vec<usize>,
+    // Pages with no outbound links
+    dead_ends: Vec<usize>,
 }
 
 impl Solution {
@@ -147,7 +210,6 @@ impl Solution {
     }
 
     /// Get the parent href for a given child href
-    #[allow(dead_code)]
     pub fn get_parent(&self, child_href: &str) -> Option<&str> {
         let child_id = *self.lookup.get(child_href)?;
         self.parents
@@ -164,7 +226,6 @@ impl Solution {
     }
 
     /// Get co-citations for a given parent href
-    #[allow(dead_code)]
     pub fn get_co_citations(&self, href: &str) -> Option<Vec<(&str, usize)>> {
         let id = *self.lookup.get(href)?;
         self.co_citations.get(&id).map(|co_citations| {
@@ -174,4 +235,111 @@ impl Solution {
                 .collect()
         })
     }
+
+    /// Get pages which can't be reached by following links from the root, sorted
+    pub fn get_unreachable(&self) -> Vec<&str> {
+        self.get_sorted(&self.unreachable)
+    }
+
+    /// Get pages with neither inbound links nor a parent page, sorted
+    pub fn get_orphans(&self) -> Vec<&str> {
+        self.get_sorted(&self.orphans)
+    }
+
+    /// Get pages which don't link anywhere, sorted
+    pub fn get_dead_ends(&self) -> Vec<&str> {
+        self.get_sorted(&self.dead_ends)
+    }
+
+    fn get_sorted(&self, ids: &[usize]) -> Vec<&str> {
+        let mut names = ids
+            .iter()
+            .map(|&id| self.names[id].as_str())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    /// Get hrefs related to a given href, most related first. Pages are ranked
+    /// by how often they are cited together with it, then by sharing its
+    /// parent, and finally by href so that the order is stable.
+    pub fn get_related(&self, href: &str) -> Vec<&str> {
+        let mut related: HashMap<&str, (usize, bool)> = HashMap::new();
+
+        for (other, count) in self.get_co_citations(href).unwrap_or_default() {
+            related.entry(other).or_default().0 = count;
+        }
+
+        let siblings = self
+            .get_parent(href)
+            .and_then(|parent| self.get_children(parent))
+            .unwrap_or_default();
+
+        for sibling in siblings {
+            if sibling != href {
+                related.entry(sibling).or_default().1 = true;
+            }
+        }
+
+        let mut related = related.into_iter().collect::<Vec<_>>();
+        related.sort_by(|(a, (a_count, a_sibling)), (b, (b_count, b_sibling))| {
+            b_count
+                .cmp(a_count)
+                .then(b_sibling.cmp(a_sibling))
+                .then(a.cmp(b))
+        });
+
+        related.into_iter().map(|(href, _)| href).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Datalog;
+
+    #[test]
+    fn related_ranks_co_citations_before_siblings() {
+        let mut datalog = Datalog::new();
+
+        for child in ["/a/x/", "/a/y/", "/a/z/", "/b/w/"] {
+            let parent = &child[..3];
+            datalog.add_parent(parent, child);
+        }
+
+        datalog.add_link("/p/", "/a/x/");
+        datalog.add_link("/p/", "/b/w/");
+        datalog.add_link("/q/", "/a/x/");
+        datalog.add_link("/q/", "/b/w/");
+        datalog.add_link("/q/", "/a/z/");
+
+        let solution = datalog.solve();
+
+        assert_eq!(solution.get_related("/a/x/"), ["/b/w/", "/a/z/", "/a/y/"]);
+    }
+
+    #[test]
+    fn maintenance_finds_orphans_dead_ends_and_unreachable_pages() {
+        let mut datalog = Datalog::new();
+
+        for page in ["/w/", "/w/a/", "/w/a/b/", "/w/c/", "/w/d/"] {
+            datalog.add_page(page);
+        }
+        datalog.add_root("/w/");
+
+        datalog.add_parent("/w/", "/w/a/");
+        datalog.add_parent("/w/a/", "/w/a/b/");
+        datalog.add_parent("/x/", "/w/c/");
+        datalog.add_parent("/x/", "/w/d/");
+
+        datalog.add_link("/w/", "/w/a/");
+        datalog.add_link("/w/a/", "/w/a/b/");
+        datalog.add_link("/w/a/b/", "/w/a/");
+        datalog.add_link("/w/c/", "/w/a/");
+
+        let solution = datalog.solve();
+
+        assert_eq!(solution.get_unreachable(), ["/w/c/", "/w/d/"]);
+        assert_eq!(solution.get_orphans(), ["/w/c/", "/w/d/"]);
+        assert_eq!(solution.get_dead_ends(), ["/w/d/"]);
+    }
 }
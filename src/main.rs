@@ -5,6 +5,7 @@ mod plugin;
 mod props;
 mod rss;
 mod ts;
+mod typst;
 mod utils;
 
 use std::fs;
@@ -18,24 +19,37 @@ use hauchiwa::loader::image::{ImageFormat, Quality};
 use hauchiwa::loader::sitemap::ChangeFrequency;
 use hauchiwa::{Blueprint, Output, TaskContext};
 use hayagriva::Library;
+use hayagriva::citationberg::IndependentStyle;
 
+use crate::model::TypstRender;
 use crate::plugin::about::add_about;
+use crate::plugin::feed::add_feed;
 use crate::plugin::home::add_home;
-use crate::plugin::posts::add_posts;
-use crate::plugin::projects::add_projects;
-use crate::plugin::slides::add_slides;
+use crate::plugin::links::add_wikilinks;
+use crate::plugin::posts::{add_posts, load_posts, parse_posts};
+use crate::plugin::projects::{add_projects, load_projects};
+use crate::plugin::slides::{add_slides, load_slides, parse_slides};
 use crate::plugin::tags::add_tags;
-use crate::plugin::twtxt::add_twtxt;
-use crate::plugin::wiki::add_teien;
+use crate::plugin::twtxt::{add_twtxt, load_twtxt};
+use crate::plugin::wiki::{add_teien, load_teien};
 use crate::props::{PropsMap, PropsSearch};
 
 /// Base path for content files
 const BASE_URL: &str = "https://kamoshi.org/";
 
+/// Source of the generated syntax highlighting partials in `styles/syntax`.
+const SYNTAX_THEME: &str = "styles/syntax/theme.json";
+
 #[derive(Parser, Debug, Clone)]
 struct Args {
     #[clap(value_enum, index = 1, default_value = "build")]
     mode: Mode,
+    /// How Typst wiki pages without a `render` setting are published
+    #[clap(value_enum, long, default_value = "pdf")]
+    typst: TypstRender,
+    /// Compile every Typst document instead of reusing cached outputs
+    #[clap(long)]
+    no_cache: bool,
 }
 
 #[derive(ValueEnum, Debug, Clone, Copy)]
@@ -53,10 +67,11 @@ struct Global {
     pub date: String,
     pub link: String,
     pub hash: String,
+    pub typst: TypstRender,
 }
 
 impl Global {
-    fn new() -> Self {
+    fn new(typst: TypstRender) -> Self {
         use hauchiwa::git;
 
         let time = chrono::Utc::now();
@@ -71,6 +86,7 @@ impl Global {
             date: time.format("%Y/%m/%d %H:%M").to_string(),
             link: "https://codeberg.org/kamov/kamoshi.org/src/commit/".into(),
             hash,
+            typst,
         }
     }
 }
@@ -113,6 +129,11 @@ struct Bibtex {
     data: Library,
 }
 
+#[derive(Debug, Clone)]
+struct Csl {
+    style: IndependentStyle,
+}
+
 fn main() -> ExitCode {
     match run() {
         Ok(()) => ExitCode::from(0),
@@ -127,15 +148,33 @@ fn run() -> Result<(), RuntimeError> {
     hauchiwa::init_logging()?;
     let args = Args::parse();
 
+    if args.no_cache {
+        typst::disable_cache();
+    }
+
     fs::write(
         "public/static/svg/footer-dither.svg",
         utils::generate_footer_dither(4, 64, 16, 42),
     )?;
 
+    // the partials have to exist before the stylesheets are first compiled
+    ts::generate_theme(SYNTAX_THEME.into(), "styles/syntax".into())?;
+
     let mut config = Blueprint::<Global>::new()
         .copy_static("public/", "")
         .copy_static("scripts/hanafuda/res/", "static/svg/hanafuda/");
 
+    // regenerates the partials when the theme changes while watching, which
+    // in turn makes the stylesheets rebuild
+    let _theme = config
+        .task()
+        .name("syntax-theme")
+        .glob(SYNTAX_THEME)?
+        .map(|_, _, input| {
+            ts::generate_theme(&input.path, "styles/syntax".into())?;
+            Ok(())
+        });
+
     let templates = config
         .load_minijinja()
         .glob("templates/**/*.jinja")?
@@ -192,29 +231,105 @@ fn run() -> Result<(), RuntimeError> {
             Ok(Bibtex { path, data })
         });
 
+    let csl = config
+        .task()
+        .name("csl")
+        .glob("content/**/*.csl")?
+        .map(|_, _, input| {
+            let data = input.read()?;
+            let text = String::from_utf8_lossy(&data);
+            let style = IndependentStyle::from_xml(&text).map_err(|err| {
+                RuntimeError::msg(format!("Failed to parse CSL style '{}': {err}", input.path))
+            })?;
+
+            Ok(Csl { style })
+        });
+
+    // documents
+    let posts_docs = load_posts(&mut config)?;
+    let slides_docs = load_slides(&mut config)?;
+    let projects_docs = load_projects(&mut config)?;
+    let (teien_docs, teien_typst) = load_teien(&mut config)?;
+    let twtxt_data = load_twtxt(&mut config)?;
+
+    // wiki links shared by every collection
+    let links = add_wikilinks(
+        &mut config,
+        posts_docs,
+        teien_docs,
+        teien_typst,
+        projects_docs,
+        slides_docs,
+    );
+
     // home
-    let home = add_home(&mut config, templates, images, styles, scripts)?;
+    let home = add_home(&mut config, templates, images, styles, scripts, links)?;
 
     // about
-    let about = add_about(&mut config, templates, images, styles)?;
+    let about = add_about(&mut config, templates, images, styles, links)?;
 
     // digital garden
-    let teien = add_teien(&mut config, templates, images, styles, bibtex)?;
+    let teien = add_teien(
+        &mut config,
+        templates,
+        teien_docs,
+        teien_typst,
+        images,
+        styles,
+        bibtex,
+        csl,
+        links,
+    )?;
 
     // twtxt
-    let twtxt = add_twtxt(&mut config, templates, styles)?;
+    let twtxt = add_twtxt(&mut config, templates, twtxt_data, styles)?;
 
     // posts
-    let (posts_data, posts) = add_posts(&mut config, templates, images, styles, scripts, bibtex)?;
+    let posts_parsed = parse_posts(&mut config, posts_docs, images, bibtex, csl, links);
+    let posts = add_posts(
+        &mut config,
+        templates,
+        posts_docs,
+        posts_parsed,
+        styles,
+        scripts,
+        bibtex,
+    )?;
 
     // slides
-    let slides = add_slides(&mut config, templates, images, styles, scripts)?;
+    let slides_html = parse_slides(&mut config, slides_docs, images, links);
+    let slides = add_slides(
+        &mut config,
+        templates,
+        slides_docs,
+        slides_html,
+        styles,
+        scripts,
+    )?;
 
     // projects
-    let projects = add_projects(&mut config, templates, styles, scripts)?;
+    let projects = add_projects(
+        &mut config,
+        templates,
+        projects_docs,
+        styles,
+        scripts,
+        links,
+    )?;
 
     // tags
-    let tags = add_tags(&mut config, templates, posts_data, styles)?;
+    let tags = add_tags(&mut config, templates, posts_docs, posts_parsed, styles)?;
+
+    // site-wide feed
+    let feed = add_feed(
+        &mut config,
+        posts_docs,
+        posts_parsed,
+        slides_docs,
+        slides_html,
+        twtxt_data,
+        teien_docs,
+    )?;
 
     // other
     let other = config
@@ -282,6 +397,7 @@ fn run() -> Result<(), RuntimeError> {
         .add(slides, ChangeFrequency::Monthly, 0.8)
         .add(projects, ChangeFrequency::Monthly, 0.8)
         .add(tags, ChangeFrequency::Monthly, 0.8)
+        .add(feed, ChangeFrequency::Daily, 0.5)
         .add(other, ChangeFrequency::Monthly, 0.8)
         .register();
 
@@ -290,11 +406,11 @@ fn run() -> Result<(), RuntimeError> {
     match args.mode {
         Mode::Build => {
             website
-                .build(Global::new())?
+                .build(Global::new(args.typst))?
                 .render_waterfall_to_file(&website, "waterfall.svg")?;
         }
         Mode::Watch => {
-            website.watch(Global::new())?;
+            website.watch(Global::new(args.typst))?;
         }
     };
 
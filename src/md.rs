@@ -1,6 +1,6 @@
 use std::{
     borrow::Cow,
-    collections::HashMap,
+    collections::{BTreeSet, HashMap, HashSet},
     fmt::Write,
     sync::{LazyLock, Mutex},
 };
@@ -8,7 +8,7 @@ use std::{
 use camino::Utf8Path;
 use comrak::{
     Arena, Node, Options, format_html_with_plugins,
-    nodes::{NodeHtmlBlock, NodeValue, NodeWikiLink},
+    nodes::{NodeBlockDirective, NodeHtmlBlock, NodeValue, NodeWikiLink},
     options::Plugins,
     parse_document,
 };
@@ -16,10 +16,14 @@ use hauchiwa::{
     Tracker,
     loader::{Document, Image, generic::DocumentMeta},
 };
+use hayagriva::citationberg::{IndependentStyle, LocaleCode};
 use hypertext::Renderable;
 use regex::Regex;
 use thiserror::Error;
 
+use crate::model::BibliographyMode;
+use crate::ts::ListingOptions;
+
 static HERN_ANALYZER: LazyLock<Option<hern_doc::Analyzer>> =
     LazyLock::new(|| hern_doc::Analyzer::new().ok());
 
@@ -31,6 +35,31 @@ pub enum MarkdownError {
     #[error("Ambiguous link '{0}'. matches multiple candidates: {1:?}")]
     WikiLinkAmbiguous(String, Vec<String>),
 
+    #[error("Heading '{1}' not found for link '{0}'")]
+    WikiLinkHeadingNotFound(String, String),
+
+    #[error("Link '{0}' points to a heading, but '{1}' has no linkable headings")]
+    WikiLinkHeadingUnsupported(String, String),
+
+    #[error("Citation key '{key}' in '{document}' not found in the bibliography")]
+    CitationNotFound { document: String, key: String },
+
+    #[error("Citation '{key}' in '{document}' was left unrendered")]
+    CitationNotRendered { document: String, key: String },
+
+    #[error("'{document}' cites {keys:?}, but there is no .bib library next to it")]
+    CitationWithoutLibrary { document: String, keys: Vec<String> },
+
+    #[error("{document}:{line}: ':::typst' should contain a single fenced code block")]
+    TypstDirective { document: String, line: usize },
+
+    #[error("{document}:{line}: Typst block failed to compile:\n{message}")]
+    Typst {
+        document: String,
+        line: usize,
+        message: String,
+    },
+
     #[error("Formatting error")]
     Format(#[from] std::fmt::Error),
 
@@ -40,12 +69,18 @@ pub enum MarkdownError {
 
 pub struct Highlighter {
     hern_session: Option<Mutex<hern_doc::SnippetSession<'static>>>,
+    /// Rest of the info string of the code block being written, handed over
+    /// from `write_code_tag` to `write_highlighted`.
+    meta: Mutex<Option<String>>,
 }
 
 impl Highlighter {
     #[allow(dead_code)]
     pub fn stateless() -> Self {
-        Self { hern_session: None }
+        Self {
+            hern_session: None,
+            meta: Mutex::default(),
+        }
     }
 
     pub fn stateful_hern() -> Self {
@@ -53,6 +88,7 @@ impl Highlighter {
             hern_session: HERN_ANALYZER
                 .as_ref()
                 .map(|analyzer| Mutex::new(analyzer.session())),
+            meta: Mutex::default(),
         }
     }
 
@@ -70,14 +106,15 @@ impl comrak::adapters::SyntaxHighlighterAdapter for Highlighter {
         code: &str,
     ) -> std::fmt::Result {
         let lang = lang.unwrap_or("text");
-        let html = if lang == "hern" {
-            let analysis = self.analyze_hern_snippet(code);
-            crate::ts::highlight_with_analysis(lang, code, analysis.as_ref())
-                .render()
-                .into_inner()
-        } else {
-            crate::ts::highlight(lang, code).render().into_inner()
+        let meta = self.meta.lock().ok().and_then(|mut meta| meta.take());
+        let options = ListingOptions::parse(meta.as_deref().unwrap_or_default());
+        let analysis = match lang {
+            "hern" => self.analyze_hern_snippet(code),
+            _ => None,
         };
+        let html = crate::ts::highlight_with_analysis(lang, code, analysis.as_ref(), options)
+            .render()
+            .into_inner();
 
         write!(output, "{}", html)?;
 
@@ -88,7 +125,14 @@ impl comrak::adapters::SyntaxHighlighterAdapter for Highlighter {
         Ok(())
     }
 
-    fn write_code_tag(&self, _: &mut dyn Write, _: HashMap<&str, Cow<str>>) -> std::fmt::Result {
+    fn write_code_tag(
+        &self,
+        _: &mut dyn Write,
+        attributes: HashMap<&str, Cow<str>>,
+    ) -> std::fmt::Result {
+        if let Ok(mut meta) = self.meta.lock() {
+            *meta = attributes.get("data-meta").map(|meta| meta.to_string());
+        }
         Ok(())
     }
 }
@@ -109,6 +153,7 @@ fn get_options() -> Options<'static> {
     options.extension.block_directive = true;
 
     options.render.r#unsafe = true;
+    options.render.full_info_string = true;
 
     options
 }
@@ -121,6 +166,7 @@ fn get_plugins<'a>(highlighter: &'a Highlighter) -> Plugins<'a> {
     plugins
 }
 
+#[derive(Clone)]
 pub struct Parsed {
     pub html: String,
     pub refs: Vec<String>,
@@ -133,7 +179,7 @@ pub fn parse(
     file_meta: &DocumentMeta,
     resolver: Option<&WikiLinkResolver>,
     images: Option<&Tracker<Image>>,
-    library: Option<&hayagriva::Library>,
+    library: Option<Library>,
 ) -> Result<Parsed, MarkdownError> {
     let arena = Arena::new();
 
@@ -143,15 +189,25 @@ pub fn parse(
 
     let root = parse_document(&arena, file_text, &options);
 
-    // Process ruby annotations
-    // [text]{ruby} -> <ruby><rb>text</rb><rp>(</rp><rt>ruby</rt><rp>)</rp></ruby>
-    process_ruby(&arena, &root);
+    // Compile Typst figures
+    // :::typst ```typst ... ``` ::: -> <figure class="typst"><svg>...</svg></figure>
+    process_typst(file_meta, &root)?;
+
+    warn_missing_languages(file_meta, &root);
+
+    // Process inline code, ruby and inline directives
+    let citations = process_inline(&arena, &root);
 
     // Process images
     // ![alt](path) -> <figure><picture>...</picture><figcaption>alt</figcaption></figure>
     process_images(file_meta, images, &arena, &root)?;
 
+    // Process block directives
+    // :::note Title ... ::: -> <aside class="admonition">...</aside>
+    process_block_directives(file_meta, &arena, &root);
+
     let mut refs = Vec::new();
+    let mut anchors = Vec::new();
 
     for node in root.descendants() {
         let mut data = node.data.borrow_mut();
@@ -173,10 +229,18 @@ pub fn parse(
                 }
             }
             NodeValue::WikiLink(link) => {
-                if let Some(resolver) = resolver {
+                // [[#Heading]] points into this same document, so it can only
+                // be checked once the outline is known
+                if let Some(heading) = link.url.strip_prefix('#') {
+                    let id = slugify(heading);
+                    anchors.push((link.url.clone(), heading.to_string(), id.clone()));
+                    data.value = NodeValue::WikiLink(NodeWikiLink {
+                        url: format!("#{id}"),
+                    });
+                } else if let Some(resolver) = resolver {
                     let url = resolver.resolve(&link.url)?;
 
-                    refs.push(url.clone());
+                    refs.push(strip_fragment(&url).to_string());
                     data.value = NodeValue::WikiLink(NodeWikiLink { url });
                 }
             }
@@ -184,15 +248,23 @@ pub fn parse(
         }
     }
 
-    process_inline_directives(&arena, &root);
-
-    let mut bibliography = None;
+    let bibliography = process_citations(file_meta, library, &citations)?;
 
-    if let Some(library) = library {
-        bibliography = process_citations(library, &root)?;
+    // A placeholder left in the tree would silently render as nothing
+    if let Some(citation) = citations.iter().find(|citation| !citation.is_rendered()) {
+        return Err(MarkdownError::CitationNotRendered {
+            document: file_meta.path.to_string(),
+            key: citation.key.clone(),
+        });
     }
 
-    let outline = process_headings(&arena, &root)?;
+    let outline = process_headings(&arena, &root, &options)?;
+
+    for (link, heading, id) in anchors {
+        if !outline.contains(&id) {
+            return Err(MarkdownError::WikiLinkHeadingNotFound(link, heading));
+        }
+    }
 
     let mut html = String::new();
     format_html_with_plugins(root, &options, &mut html, &plugins)?;
@@ -205,6 +277,60 @@ pub fn parse(
     })
 }
 
+/// Collects the raw targets of all `[[wikilinks]]` in a document, without
+/// resolving or rendering anything.
+pub fn wikilinks(file_text: &str) -> Vec<String> {
+    let arena = Arena::new();
+    let options = get_options();
+    let root = parse_document(&arena, file_text, &options);
+
+    root.descendants()
+        .filter_map(|node| match &node.data.borrow().value {
+            NodeValue::WikiLink(link) => Some(link.url.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Rewrites the inline syntax extensions, which has to happen the same way
+/// for rendering and for collecting headings, so that slugs agree.
+fn process_inline<'arena, 'a>(
+    arena: &'a Arena<'arena>,
+    root: &'a Node<'arena>,
+) -> Vec<Citation<'arena>>
+where
+    'a: 'arena,
+{
+    // Process highlighted inline code
+    // `let x = 5;`{rust} -> <code class="syntax">...</code>
+    process_inline_code(root);
+
+    // Process ruby annotations
+    // [text]{ruby} -> <ruby><rb>text</rb><rp>(</rp><rt>ruby</rt><rp>)</rp></ruby>
+    process_ruby(arena, root);
+
+    // Process inline directives
+    // :icon[path] -> <img class="inline-icon">
+    process_inline_directives(arena, root)
+}
+
+/// Collects the heading ids of a document, as they will be assigned when the
+/// document is rendered.
+pub fn headings(file_text: &str) -> Vec<String> {
+    let arena = Arena::new();
+    let options = get_options();
+    let root = parse_document(&arena, file_text, &options);
+
+    process_inline(&arena, &root);
+
+    let mut counts = HashMap::new();
+
+    root.descendants()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::Heading(_)))
+        .map(|node| unique_slug(&mut counts, &extract_text(&node)))
+        .collect()
+}
+
 // hashed images
 
 fn process_images<'arena, 'a>(
@@ -343,29 +469,27 @@ fn parse_latex(math: &str, is_display: bool) -> Result<String, MarkdownError> {
 
 pub struct WikiLinkResolver {
     index: HashMap<String, Vec<String>>,
+    /// href -> heading ids, for pages whose headings are known
+    anchors: HashMap<String, HashSet<String>>,
+    /// lowercase title or alias -> hrefs
+    aliases: HashMap<String, Vec<String>>,
 }
 
 impl WikiLinkResolver {
     pub fn new() -> Self {
         Self {
             index: HashMap::new(),
+            anchors: HashMap::new(),
+            aliases: HashMap::new(),
         }
     }
 
-    pub fn from_assets<T>(assets: &Tracker<Document<T>>) -> Self
-    where
-        T: Clone,
-    {
-        let mut resolver = Self::new();
-        resolver.add_all(assets);
-        resolver
-    }
-
     pub fn add<T>(&mut self, doc: &Document<T>)
     where
         T: Clone,
     {
         self.add_href(&doc.meta.href);
+        self.add_anchors(&doc.meta.href, headings(&doc.text));
     }
 
     /// Add a generated page href that does not come from a Markdown document,
@@ -381,34 +505,61 @@ impl WikiLinkResolver {
         }
     }
 
-    pub fn add_all<T>(&mut self, docs: &Tracker<Document<T>>)
-    where
-        T: Clone,
-    {
-        for (_, doc) in docs {
-            self.add(doc);
+    /// Make a page reachable by the given names in addition to its file stem,
+    /// such as a wiki page's title and aliases.
+    pub fn add_aliases<'a>(&mut self, href: &str, names: impl IntoIterator<Item = &'a str>) {
+        for name in names {
+            let hrefs = self.aliases.entry(name.to_lowercase()).or_default();
+            if !hrefs.iter().any(|it| it == href) {
+                hrefs.push(href.to_string());
+            }
         }
     }
 
+    fn add_anchors(&mut self, href: &str, ids: Vec<String>) {
+        self.anchors
+            .entry(href.to_string())
+            .or_default()
+            .extend(ids);
+    }
+
+    /// Resolves a link such as `[[tech/Rust]]` or `[[Rust#Ownership]]` to an
+    /// href, checking that the heading exists when one is given.
     pub fn resolve(&self, link: &str) -> Result<String, MarkdownError> {
+        let Some((page, heading)) = link.split_once('#') else {
+            return self.resolve_page(link);
+        };
+
+        let href = self.resolve_page(page)?;
+        let id = slugify(heading);
+
+        match self.anchors.get(&href) {
+            Some(ids) if ids.contains(&id) => Ok(format!("{href}#{id}")),
+            None => Err(MarkdownError::WikiLinkHeadingUnsupported(
+                link.to_string(),
+                href,
+            )),
+            _ => Err(MarkdownError::WikiLinkHeadingNotFound(
+                link.to_string(),
+                heading.to_string(),
+            )),
+        }
+    }
+
+    fn resolve_page(&self, link: &str) -> Result<String, MarkdownError> {
         // Extract stem (e.g., "a/b/Note" -> "note")
         let link_path = Utf8Path::new(link);
 
-        let stem = link_path
-            .file_stem()
-            .map(|s| s.to_lowercase())
-            .ok_or_else(|| MarkdownError::WikiLinkNotFound(link.to_string()))?;
+        let stem = link_path.file_stem().map(|s| s.to_lowercase());
 
         // Check if any file with this name exists
-        let candidates = self
-            .index
-            .get(&stem)
-            .ok_or_else(|| MarkdownError::WikiLinkNotFound(link.to_string()))?;
+        let candidates = stem.and_then(|stem| self.index.get(&stem));
 
         // Filter candidates that match the explicit path provided in the link
         // e.g. [[tech/Rust]] matches "/wiki/tech/Rust.html" but not "/wiki/game/Rust.html"
-        let matches: Vec<String> = candidates
-            .iter()
+        let mut matches: Vec<String> = candidates
+            .into_iter()
+            .flatten()
             .filter(|candidate| {
                 Utf8Path::new(candidate)
                     .with_extension("")
@@ -417,6 +568,15 @@ impl WikiLinkResolver {
             .cloned()
             .collect();
 
+        // Titles and aliases match the whole link, e.g. [[Linear Algebra]]
+        if let Some(hrefs) = self.aliases.get(&link.to_lowercase()) {
+            for href in hrefs {
+                if !matches.contains(href) {
+                    matches.push(href.clone());
+                }
+            }
+        }
+
         match matches.as_slice() {
             [] => Err(MarkdownError::WikiLinkNotFound(link.to_string())),
             [it] => Ok(it.clone()), // Perfect match
@@ -425,6 +585,11 @@ impl WikiLinkResolver {
     }
 }
 
+/// Drops the `#heading` part of a resolved link, leaving the page href.
+pub fn strip_fragment(href: &str) -> &str {
+    href.split_once('#').map_or(href, |(page, _)| page)
+}
+
 // ruby matcher
 static RE_RUBY: LazyLock<Regex> = LazyLock::new(|| {
     // Matches [Kanji]{kana}
@@ -504,30 +669,129 @@ where
     }
 }
 
+/// Reports the languages of code blocks and highlighted inline code which
+/// have no highlighting configured, once per language and document.
+fn warn_missing_languages<'a>(file_meta: &DocumentMeta, root: &'a Node<'a>) {
+    let mut missing = BTreeSet::new();
+
+    for node in root.descendants() {
+        let lang = match &node.data.borrow().value {
+            NodeValue::CodeBlock(block) if block.fenced => block
+                .info
+                .split_whitespace()
+                .next()
+                .unwrap_or("")
+                .to_string(),
+            NodeValue::Code(_) => {
+                let Some(next) = node.next_sibling() else {
+                    continue;
+                };
+                let NodeValue::Text(text) = &next.data.borrow().value else {
+                    continue;
+                };
+                let Some(caps) = RE_CODE_LANG.captures(text) else {
+                    continue;
+                };
+                caps[1].to_string()
+            }
+            _ => continue,
+        };
+
+        if crate::ts::configs::is_missing(&lang) {
+            missing.insert(lang);
+        }
+    }
+
+    for lang in missing {
+        hauchiwa::tracing::warn!(
+            "{}: no syntax highlighting configured for '{lang}' code",
+            file_meta.path
+        );
+    }
+}
+
+// inline code
+
+static RE_CODE_LANG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\{([\w+#.-]+)\}").expect("Invalid regex"));
+
+fn process_inline_code<'a>(root: &'a Node<'a>) {
+    let nodes = root
+        .descendants()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::Code(_)))
+        .collect::<Vec<_>>();
+
+    for node in nodes {
+        let Some(next) = node.next_sibling() else {
+            continue;
+        };
+
+        let lang = {
+            let mut next = next.data.borrow_mut();
+            let NodeValue::Text(text) = &mut next.value else {
+                continue;
+            };
+            let Some(caps) = RE_CODE_LANG.captures(text) else {
+                continue;
+            };
+
+            let lang = caps[1].to_string();
+            let rest = text[caps[0].len()..].to_string();
+            *text = rest.into();
+            lang
+        };
+
+        if matches!(&next.data.borrow().value, NodeValue::Text(text) if text.is_empty()) {
+            next.detach();
+        }
+
+        // headings keep plain code, their text is needed for the slug
+        let in_heading = node
+            .ancestors()
+            .any(|node| matches!(node.data.borrow().value, NodeValue::Heading(_)));
+        if in_heading {
+            continue;
+        }
+
+        let mut data = node.data.borrow_mut();
+        if let NodeValue::Code(code) = &data.value {
+            let html = crate::ts::highlight_inline(&lang, &code.literal);
+            data.value = NodeValue::HtmlInline(html);
+        }
+    }
+}
+
 // inline directive
 
 static RE_DIRECTIVE_INLINE: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r":(\w+)\[(.*?)\]").expect("Invalid regex"));
 
-fn render_directive_inline<'a>(arena: &'a Arena<'a>, name: String, content: String) -> Node<'a> {
-    match name.as_str() {
+/// A `:cite[key]` reference, its node is an empty [`NodeValue::Raw`] until the
+/// citation is rendered by [`process_citations`]. Raw nodes never come from the
+/// parser, so a placeholder can't be mistaken for anything in the source.
+struct Citation<'a> {
+    node: Node<'a>,
+    key: String,
+}
+
+impl Citation<'_> {
+    fn is_rendered(&self) -> bool {
+        !matches!(self.node.data.borrow().value, NodeValue::Raw(_))
+    }
+}
+
+fn render_directive_inline<'a>(arena: &'a Arena<'a>, name: &str, content: &str) -> Node<'a> {
+    match name {
         "icon" => {
             let html = format!(
                 r#"<img class="inline-icon" src="{}">"#,
-                crate::utils::escape_html_attr(&content)
+                crate::utils::escape_html_attr(content)
             );
             arena.alloc(NodeValue::HtmlInline(html).into())
         }
         "cite" => {
-            // Repurpose Math node for citations
-            arena.alloc(
-                NodeValue::Math(comrak::nodes::NodeMath {
-                    dollar_math: false,
-                    display_math: false,
-                    literal: content.to_string(),
-                })
-                .into(),
-            )
+            // Placeholder, the citation is rendered once the bibliography is known
+            arena.alloc(NodeValue::Raw(String::new()).into())
         }
         _ => {
             // Fallback: If unknown, perhaps render as plain text or a warning
@@ -537,11 +801,15 @@ fn render_directive_inline<'a>(arena: &'a Arena<'a>, name: String, content: Stri
     }
 }
 
-fn process_inline_directives<'arena, 'a>(arena: &'a Arena<'arena>, root: &'a Node<'arena>)
+fn process_inline_directives<'arena, 'a>(
+    arena: &'a Arena<'arena>,
+    root: &'a Node<'arena>,
+) -> Vec<Citation<'arena>>
 where
     'a: 'arena,
 {
     let mut nodes_to_modify = Vec::new();
+    let mut citations = Vec::new();
 
     // Scan for directives in all Text nodes
     for node in root.descendants() {
@@ -576,7 +844,14 @@ where
                 node.insert_before(pre_node);
             }
 
-            let directive_node = render_directive_inline(arena, name, content);
+            let directive_node = render_directive_inline(arena, &name, &content);
+
+            if name == "cite" {
+                citations.push(Citation {
+                    node: directive_node,
+                    key: content,
+                });
+            }
 
             node.insert_before(directive_node);
             last_idx = range.end;
@@ -592,6 +867,233 @@ where
         // Remove the original text node
         node.detach();
     }
+
+    citations
+}
+
+// typst
+
+/// Compiles `:::typst` directives to inline SVG figures. The source goes in a
+/// fenced code block inside the directive, so it isn't parsed as Markdown:
+///
+/// ````markdown
+/// :::typst
+/// ```typst
+/// $ sum_(k=1)^n k = (n(n+1)) / 2 $
+/// ```
+/// :::
+/// ````
+///
+/// A bare ```` ```typst ```` block is left alone and rendered as a listing.
+fn process_typst<'a>(file_meta: &DocumentMeta, root: &'a Node<'a>) -> Result<(), MarkdownError> {
+    let mut nodes = Vec::new();
+
+    for node in root.descendants() {
+        let data = node.data.borrow();
+        if let NodeValue::BlockDirective(directive) = &data.value
+            && directive.info.split_whitespace().next() == Some("typst")
+        {
+            nodes.push((node, data.sourcepos.start.line));
+        }
+    }
+
+    for (node, line) in nodes {
+        let mut children = node.children();
+        let (Some(child), None) = (children.next(), children.next()) else {
+            return Err(MarkdownError::TypstDirective {
+                document: file_meta.path.to_string(),
+                line,
+            });
+        };
+
+        let svg = {
+            let data = child.data.borrow();
+            let NodeValue::CodeBlock(block) = &data.value else {
+                return Err(MarkdownError::TypstDirective {
+                    document: file_meta.path.to_string(),
+                    line,
+                });
+            };
+
+            crate::typst::render_typst(&block.literal).map_err(|err| MarkdownError::Typst {
+                document: file_meta.path.to_string(),
+                line: data.sourcepos.start.line,
+                message: err.to_string(),
+            })?
+        };
+
+        child.detach();
+        node.data.borrow_mut().value = NodeValue::HtmlBlock(NodeHtmlBlock {
+            block_type: 0,
+            literal: format!("<figure class=\"typst\">{svg}</figure>"),
+        });
+    }
+
+    Ok(())
+}
+
+// block directive
+
+/// Handler for a `:::name args` container, it receives the directive node and
+/// is expected to replace it with the rendered HTML around its children.
+type BlockDirectiveHandler = for<'a> fn(&'a Arena<'a>, Node<'a>, &str, &str);
+
+/// Registry of the built-in block directives.
+static BLOCK_DIRECTIVES: &[(&str, BlockDirectiveHandler)] = &[
+    ("note", render_directive_admonition),
+    ("tip", render_directive_admonition),
+    ("warning", render_directive_admonition),
+    ("details", render_directive_details),
+    ("columns", render_directive_columns),
+    ("figures", render_directive_figures),
+    ("sidenote", render_directive_div),
+];
+
+fn process_block_directives<'a>(
+    file_meta: &DocumentMeta,
+    arena: &'a Arena<'a>,
+    root: &'a Node<'a>,
+) {
+    let mut nodes = Vec::new();
+
+    for node in root.descendants() {
+        let data = node.data.borrow();
+        if let NodeValue::BlockDirective(directive) = &data.value {
+            let NodeBlockDirective { info, .. } = directive.as_ref();
+            nodes.push((node, info.clone(), data.sourcepos.start.line));
+        }
+    }
+
+    // Nested directives stay attached to the tree when their parent is
+    // unwrapped, so the order in which we visit them does not matter.
+    for (node, info, line) in nodes {
+        let (name, args) = match info.split_once(char::is_whitespace) {
+            Some((name, args)) => (name, args.trim()),
+            None => (info.as_str(), ""),
+        };
+
+        match BLOCK_DIRECTIVES.iter().find(|(key, _)| *key == name) {
+            Some((_, handler)) => handler(arena, node, name, args),
+            None => {
+                hauchiwa::tracing::warn!(
+                    "{}:{line}: unknown block directive ':::{name}'",
+                    file_meta.path
+                );
+                render_directive_div(arena, node, name, args);
+            }
+        }
+    }
+}
+
+/// Replaces the directive `node` with its children surrounded by raw HTML.
+fn wrap_children<'a>(arena: &'a Arena<'a>, node: Node<'a>, open: String, close: String) {
+    let html = |literal: String| {
+        arena.alloc(
+            NodeValue::HtmlBlock(NodeHtmlBlock {
+                block_type: 0,
+                literal,
+            })
+            .into(),
+        )
+    };
+
+    if !open.is_empty() {
+        node.insert_before(html(open));
+    }
+
+    for child in node.children().collect::<Vec<_>>() {
+        child.detach();
+        node.insert_before(child);
+    }
+
+    if !close.is_empty() {
+        node.insert_before(html(close));
+    }
+
+    node.detach();
+}
+
+fn render_directive_admonition<'a>(arena: &'a Arena<'a>, node: Node<'a>, name: &str, args: &str) {
+    let title = match args {
+        "" => {
+            let mut chars = name.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        }
+        args => args.to_string(),
+    };
+
+    let open = format!(
+        "<aside class=\"admonition admonition-{name}\">\n<p class=\"admonition__title\">{}</p>\n",
+        crate::utils::escape_html_text(&title)
+    );
+
+    wrap_children(arena, node, open, "</aside>\n".into());
+}
+
+/// Plain `<div class="{name}">` wrapper, left for the stylesheets to handle.
+fn render_directive_div<'a>(arena: &'a Arena<'a>, node: Node<'a>, name: &str, _: &str) {
+    let open = format!("<div class=\"{}\">\n", crate::utils::escape_html_attr(name));
+
+    wrap_children(arena, node, open, "</div>\n".into());
+}
+
+fn render_directive_details<'a>(arena: &'a Arena<'a>, node: Node<'a>, _: &str, args: &str) {
+    let summary = if args.is_empty() { "Details" } else { args };
+
+    let open = format!(
+        "<details>\n<summary>{}</summary>\n",
+        crate::utils::escape_html_text(summary)
+    );
+
+    wrap_children(arena, node, open, "</details>\n".into());
+}
+
+/// Columns are separated with thematic breaks (`---`) inside the directive.
+fn render_directive_columns<'a>(arena: &'a Arena<'a>, node: Node<'a>, _: &str, _: &str) {
+    let html = |literal: &str| {
+        arena.alloc(
+            NodeValue::HtmlBlock(NodeHtmlBlock {
+                block_type: 0,
+                literal: literal.to_string(),
+            })
+            .into(),
+        )
+    };
+
+    for child in node.children().collect::<Vec<_>>() {
+        if matches!(child.data.borrow().value, NodeValue::ThematicBreak) {
+            child.insert_before(html("</div>\n<div class=\"column\">\n"));
+            child.detach();
+        }
+    }
+
+    wrap_children(
+        arena,
+        node,
+        "<div class=\"columns\">\n<div class=\"column\">\n".into(),
+        "</div>\n</div>\n".into(),
+    );
+}
+
+fn render_directive_figures<'a>(arena: &'a Arena<'a>, node: Node<'a>, _: &str, args: &str) {
+    let close = if args.is_empty() {
+        "</div>\n</figure>\n".to_string()
+    } else {
+        format!(
+            "</div>\n<figcaption>{}</figcaption>\n</figure>\n",
+            crate::utils::escape_html_text(args)
+        )
+    };
+
+    wrap_children(
+        arena,
+        node,
+        "<figure class=\"figure-group\">\n<div class=\"figure-group__items\">\n".into(),
+        close,
+    );
 }
 
 // citations
@@ -599,68 +1101,135 @@ where
 static LOCALE: LazyLock<Vec<hayagriva::citationberg::Locale>> =
     LazyLock::new(hayagriva::archive::locales);
 
-static STYLE: LazyLock<hayagriva::citationberg::IndependentStyle> = LazyLock::new(|| {
+/// Default citation style, used when a document does not pick its own.
+pub static STYLE: LazyLock<IndependentStyle> = LazyLock::new(|| {
     match hayagriva::archive::ArchivedStyle::InstituteOfElectricalAndElectronicsEngineers.get() {
         hayagriva::citationberg::Style::Independent(style) => style,
         hayagriva::citationberg::Style::Dependent(_) => unreachable!(),
     }
 });
 
-fn process_citations<'arena, 'a>(
-    library: &hayagriva::Library,
-    root: &'a Node<'arena>,
-) -> Result<Option<Vec<String>>, std::fmt::Error> {
+/// Bibliography attached to a document, together with the style used to
+/// render its citations.
+#[derive(Clone, Copy)]
+pub struct Library<'a> {
+    pub data: &'a hayagriva::Library,
+    pub style: &'a IndependentStyle,
+    pub mode: BibliographyMode,
+    /// Locale requested by the document, if any.
+    pub locale: Option<&'a str>,
+}
+
+/// Look up one of the styles bundled with hayagriva by its short name, such
+/// as `apa` or `chicago-author-date`.
+pub fn archived_style(name: &str) -> Option<IndependentStyle> {
+    match hayagriva::archive::ArchivedStyle::by_name(name)?.get() {
+        hayagriva::citationberg::Style::Independent(style) => Some(style),
+        hayagriva::citationberg::Style::Dependent(_) => None,
+    }
+}
+
+fn process_citations(
+    file_meta: &DocumentMeta,
+    library: Option<Library>,
+    citations: &[Citation],
+) -> Result<Option<Vec<String>>, MarkdownError> {
     use hayagriva::{
         BibliographyDriver, BibliographyRequest, BufWriteFormat, CitationItem, CitationRequest,
     };
 
-    // Check math nodes that exist
-    // We store the Node reference and the key to avoid borrowing issues later
+    let Some(Library {
+        data: library,
+        style,
+        mode,
+        locale,
+    }) = library
+    else {
+        if citations.is_empty() {
+            return Ok(None);
+        }
+
+        return Err(MarkdownError::CitationWithoutLibrary {
+            document: file_meta.path.to_string(),
+            keys: citations.iter().map(|c| c.key.clone()).collect(),
+        });
+    };
+
+    // Every cited key has to exist in the library
     let mut candidates = Vec::new();
 
-    for node in root.descendants() {
-        if let NodeValue::Math(math) = &node.data.borrow().value {
-            // Only process inline math that matches a library key
-            if !math.display_math && library.get(&math.literal).is_some() {
-                candidates.push((node, math.literal.clone()));
-            }
-        }
+    for Citation { node, key } in citations {
+        let entry = library
+            .get(key)
+            .ok_or_else(|| MarkdownError::CitationNotFound {
+                document: file_meta.path.to_string(),
+                key: key.clone(),
+            })?;
+
+        candidates.push((node, entry));
     }
 
-    if candidates.is_empty() {
-        return Ok(None);
+    // Uncited entries are only worth reporting when they are left out of the
+    // rendered bibliography.
+    if mode == BibliographyMode::Cited {
+        let unused = library
+            .iter()
+            .map(|entry| entry.key())
+            .filter(|key| citations.iter().all(|c| c.key != *key))
+            .collect::<Vec<_>>();
+
+        if !unused.is_empty() {
+            hauchiwa::tracing::warn!(
+                "{}: unused bibliography entries: {}",
+                file_meta.path,
+                unused.join(", ")
+            );
+        }
+
+        if candidates.is_empty() {
+            return Ok(None);
+        }
     }
 
+    let locale = locale
+        .map(|code| LocaleCode(code.to_string()))
+        .or_else(|| style.default_locale.clone());
+
     let mut driver = BibliographyDriver::new();
 
     // Register each citation found in the text
-    for (_, key) in &candidates {
-        if let Some(entry) = library.get(key) {
-            driver.citation(CitationRequest::from_items(
-                vec![CitationItem::with_entry(entry)],
-                &STYLE,
-                &LOCALE,
-            ));
-        }
+    for &(_, entry) in &candidates {
+        driver.citation(CitationRequest::new(
+            vec![CitationItem::with_entry(entry)],
+            style,
+            locale.clone(),
+            &LOCALE,
+            None,
+        ));
     }
 
     // This ensures *every* item in the library appears in the final
     // bibliography list, not just the ones cited in the text.
-    driver.citation(CitationRequest::from_items(
-        library.iter().map(CitationItem::with_entry).collect(),
-        &STYLE,
-        &LOCALE,
-    ));
+    if mode == BibliographyMode::All {
+        driver.citation(CitationRequest::new(
+            library.iter().map(CitationItem::with_entry).collect(),
+            style,
+            locale.clone(),
+            &LOCALE,
+            None,
+        ));
+    }
 
     // Render results
     let result = driver.finish(BibliographyRequest {
-        style: &STYLE,
-        locale: None,
+        style,
+        locale,
         locale_files: &LOCALE,
     });
 
-    // Swap Math nodes for HTML <cite> tags. Skip the last entry because that
-    // corresponds to the "fake pass" that includes all items.
+    // Fill placeholder nodes with HTML <cite> tags. In the "all" mode the last
+    // entry corresponds to the "fake pass" that includes all items, the zip
+    // skips it.
     for ((node, _), item) in candidates.iter().zip(result.citations.iter()) {
         let mut buf = String::from("<cite>");
 
@@ -697,6 +1266,18 @@ pub struct Heading {
 #[derive(Debug, Clone)]
 pub struct Outline(pub Vec<Heading>);
 
+impl Outline {
+    pub fn contains(&self, id: &str) -> bool {
+        fn find(headings: &[Heading], id: &str) -> bool {
+            headings
+                .iter()
+                .any(|heading| heading.id == id || find(&heading.children, id))
+        }
+
+        find(&self.0, id)
+    }
+}
+
 impl From<Vec<(String, String, usize)>> for Outline {
     fn from(flat_vec: Vec<(String, String, usize)>) -> Self {
         let mut res = Vec::<Heading>::new();
@@ -768,6 +1349,7 @@ impl hypertext::Renderable for Outline {
 fn process_headings<'a>(
     arena: &'a Arena<'a>,
     root: &'a Node<'a>,
+    options: &Options,
 ) -> Result<Outline, MarkdownError> {
     let mut flat_headings = Vec::new();
     let mut counts = HashMap::new();
@@ -782,17 +1364,7 @@ fn process_headings<'a>(
 
     for (node, level) in nodes_to_process {
         let text = extract_text(&node);
-
-        let mut slug = text.to_lowercase().replace(' ', "-");
-        match counts.get_mut(&slug) {
-            Some(count) => {
-                *count += 1;
-                slug = format!("{slug}-{count}");
-            }
-            None => {
-                counts.insert(slug.clone(), 0);
-            }
-        }
+        let slug = unique_slug(&mut counts, &text);
 
         // We create a temporary root to render just the children of this heading
         let inner_html = {
@@ -804,7 +1376,7 @@ fn process_headings<'a>(
             }
 
             let mut html = String::new();
-            comrak::format_html(root, &comrak::Options::default(), &mut html)?;
+            comrak::format_html(root, options, &mut html)?;
 
             html
         };
@@ -834,6 +1406,25 @@ fn process_headings<'a>(
     Ok(Outline::from(flat_headings))
 }
 
+fn slugify(text: &str) -> String {
+    text.to_lowercase().replace(' ', "-")
+}
+
+/// Slugifies a heading, numbering repeated ones (`intro`, `intro-1`, ...)
+fn unique_slug(counts: &mut HashMap<String, usize>, text: &str) -> String {
+    let slug = slugify(text);
+    match counts.get_mut(&slug) {
+        Some(count) => {
+            *count += 1;
+            format!("{slug}-{count}")
+        }
+        None => {
+            counts.insert(slug.clone(), 0);
+            slug
+        }
+    }
+}
+
 /// Helper to recursively extract text from a node's children
 fn extract_text(node: &Node) -> String {
     let mut buf = String::new();
@@ -849,3 +1440,184 @@ fn extract_text(node: &Node) -> String {
 
     buf
 }
+
+#[cfg(test)]
+mod tests {
+    use hauchiwa::loader::generic::DocumentMeta;
+
+    fn meta() -> DocumentMeta {
+        DocumentMeta {
+            path: "content/wiki/test.md".into(),
+            base: None,
+            href: "/wiki/test/".into(),
+        }
+    }
+
+    #[test]
+    fn block_directives_wrap_their_children() {
+        let text = ":::note Heads up\nBody text.\n:::\n\n::: sidenote\nAside.\n:::\n\n:::unknown\nPlain.\n:::\n";
+        let html = super::parse(text, &meta(), None, None, None).unwrap().html;
+
+        assert!(html.contains(r#"<aside class="admonition admonition-note">"#));
+        assert!(html.contains("Heads up"));
+        assert!(html.contains("<p>Body text.</p>"));
+        assert!(html.contains("<div class=\"sidenote\">\n<p>Aside.</p>\n</div>"));
+        assert!(html.contains("<div class=\"unknown\">\n<p>Plain.</p>\n</div>"));
+    }
+
+    #[test]
+    fn typst_directive_needs_a_code_block() {
+        let text = "```typst\n#set page(width: auto)\n```\n";
+        let html = super::parse(text, &meta(), None, None, None).unwrap().html;
+
+        assert!(html.contains(r#"data-lang="typst""#));
+        assert!(!html.contains(r#"<figure class="typst">"#));
+
+        let text = "Intro.\n\n:::typst\n$ x^2 $\n:::\n";
+        let err = super::parse(text, &meta(), None, None, None).err();
+
+        assert!(matches!(
+            err,
+            Some(super::MarkdownError::TypstDirective { line: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn inline_code_is_highlighted_with_a_language_suffix() {
+        let text = "Bind with `let x = 5;`{rust}, not `let`.";
+        let html = super::parse(text, &meta(), None, None, None).unwrap().html;
+
+        assert!(html.contains(r#"<code class="syntax" data-lang="rust"><span class="keyword"#));
+        assert!(html.contains("</code>, not <code>let</code>."));
+        assert!(!html.contains("{rust}"));
+
+        let text = "## The `Option<T>`{rust} type\n\nSee [[#The Option<T> type]].";
+        let parsed = super::parse(text, &meta(), None, None, None).unwrap();
+
+        assert!(parsed.html.contains(
+            r#"<h2 id="the-option&lt;t&gt;-type">The <code>Option&lt;T&gt;</code> type</h2>"#
+        ));
+        assert!(parsed.outline.contains("the-option<t>-type"));
+        assert!(!parsed.html.contains("{rust}"));
+    }
+
+    #[test]
+    fn citations_require_a_matching_library_entry() {
+        use super::MarkdownError;
+
+        let text = "As shown in :cite[knuth84].";
+
+        let result = super::parse(text, &meta(), None, None, None);
+        assert!(matches!(
+            result,
+            Err(MarkdownError::CitationWithoutLibrary { .. })
+        ));
+
+        let library = hayagriva::io::from_biblatex_str(
+            "@book{lamport94, title={LaTeX}, author={Lamport, Leslie}, year={1994}}",
+        )
+        .unwrap();
+
+        let library = super::Library {
+            data: &library,
+            style: &super::STYLE,
+            mode: super::BibliographyMode::Cited,
+            locale: None,
+        };
+
+        let result = super::parse(text, &meta(), None, None, Some(library));
+        assert!(matches!(
+            result,
+            Err(MarkdownError::CitationNotFound { key, .. }) if key == "knuth84"
+        ));
+    }
+
+    #[test]
+    fn citations_are_rendered_in_place() {
+        let library = hayagriva::io::from_biblatex_str(
+            "@book{lamport94, title={LaTeX}, author={Lamport, Leslie}, year={1994}}",
+        )
+        .unwrap();
+
+        let library = super::Library {
+            data: &library,
+            style: &super::STYLE,
+            mode: super::BibliographyMode::Cited,
+            locale: None,
+        };
+
+        let text = "As shown in :cite[lamport94], twice :cite[lamport94].";
+        let parsed = super::parse(text, &meta(), None, None, Some(library)).unwrap();
+
+        assert_eq!(parsed.html.matches("<cite>").count(), 2);
+        assert_eq!(parsed.bibliography.map(|bib| bib.len()), Some(1));
+    }
+
+    #[test]
+    fn wikilinks_resolve_titles_and_aliases() {
+        use super::{MarkdownError, WikiLinkResolver};
+
+        let mut resolver = WikiLinkResolver::new();
+        resolver.add_href("/wiki/math/linear-algebra/");
+        resolver.add_aliases("/wiki/math/linear-algebra/", ["Linear Algebra", "線形代数"]);
+        resolver.add_href("/wiki/tech/rust/");
+        resolver.add_aliases("/wiki/tech/rust/", ["Rust"]);
+        resolver.add_href("/wiki/game/oxide/");
+        resolver.add_aliases("/wiki/game/oxide/", ["Rust"]);
+
+        for link in ["linear-algebra", "Linear Algebra", "線形代数"] {
+            assert_eq!(
+                resolver.resolve(link).unwrap(),
+                "/wiki/math/linear-algebra/"
+            );
+        }
+
+        assert!(matches!(
+            resolver.resolve("rust"),
+            Err(MarkdownError::WikiLinkAmbiguous(_, hrefs)) if hrefs.len() == 2
+        ));
+        assert_eq!(resolver.resolve("tech/rust").unwrap(), "/wiki/tech/rust/");
+    }
+
+    #[test]
+    fn wikilinks_check_heading_anchors() {
+        use super::{MarkdownError, WikiLinkResolver};
+
+        let mut resolver = WikiLinkResolver::new();
+        resolver.add_href("/wiki/rust/");
+        resolver.add_anchors(
+            "/wiki/rust/",
+            super::headings("# Intro\n\n## Borrow checker\n"),
+        );
+
+        let text = "See [[rust#Borrow checker]] and [[#Setup]].\n\n## Setup\n";
+        let parsed = super::parse(text, &meta(), Some(&resolver), None, None).unwrap();
+        assert!(parsed.html.contains(r#"href="/wiki/rust/#borrow-checker""#));
+        assert!(parsed.html.contains(r##"href="#setup""##));
+        assert_eq!(parsed.refs, ["/wiki/rust/"]);
+
+        let result = super::parse("[[rust#Lifetimes]]", &meta(), Some(&resolver), None, None);
+        assert!(matches!(
+            result,
+            Err(MarkdownError::WikiLinkHeadingNotFound(_, heading)) if heading == "Lifetimes"
+        ));
+
+        let result = super::parse("[[#Missing]]", &meta(), None, None, None);
+        assert!(matches!(
+            result,
+            Err(MarkdownError::WikiLinkHeadingNotFound(..))
+        ));
+
+        // headings are slugged after the inline syntax is rewritten
+        assert_eq!(
+            super::headings("## Boxed :icon[box.png]pointers\n"),
+            ["boxed-pointers"]
+        );
+
+        resolver.add_href("/slides/intro/");
+        assert!(matches!(
+            resolver.resolve("intro#Agenda"),
+            Err(MarkdownError::WikiLinkHeadingUnsupported(..))
+        ));
+    }
+}
@@ -1,8 +1,14 @@
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
+use std::sync::LazyLock;
 
-use chrono::{DateTime, Utc};
+use blake2::{Blake2b, Digest, digest::consts::U32};
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use data_encoding::BASE32_NOPAD;
+use regex::Regex;
 // use hauchiwa::{WithFile, loader::Content};
 use serde::Deserialize;
+use thiserror::Error;
 
 #[derive(Clone, Deserialize)]
 pub struct Pubkey {
@@ -10,32 +16,223 @@ pub struct Pubkey {
     pub data: String,
 }
 
+/// A single twtxt entry.
 #[derive(Deserialize, Clone)]
 pub struct MicroblogEntry {
     #[serde(with = "isodate")]
     pub date: DateTime<Utc>,
+    /// The text exactly as written in the feed.
     pub text: String,
+    /// Yarn-style hash identifying the entry in replies.
+    pub hash: String,
+    /// Hash of the entry this one replies to, taken from its `(#hash)` subject.
+    pub reply_to: Option<String>,
+    /// Markdown source with the mentions and the reply subject made into links.
+    pub body: String,
+}
+
+/// Metadata from the `# key = value` comments heading a twtxt feed.
+#[derive(Deserialize, Clone, Default)]
+pub struct MicroblogMeta {
+    pub nick: Option<String>,
+    /// The first `url` of the feed, it's the one entry hashes are based on.
+    pub url: Option<String>,
+    pub description: Option<String>,
+    pub avatar: Option<String>,
+    /// Followed feeds as `(nick, url)` pairs.
+    #[serde(default)]
+    pub follow: Vec<(String, String)>,
 }
 
 #[derive(Clone, Deserialize)]
 pub struct Microblog {
+    #[serde(default)]
+    pub meta: MicroblogMeta,
     pub entries: Vec<MicroblogEntry>,
     pub data: String,
 }
 
-impl FromStr for MicroblogEntry {
-    type Err = chrono::ParseError;
+static RE_SUBJECT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\(#(?:<([a-z2-7]{7})(?:\s+([^\s>]+))?>|([a-z2-7]{7}))\)").expect("Invalid regex")
+});
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut parts = s.splitn(2, char::is_whitespace);
+static RE_MENTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"@<(?:([^\s>]+)\s+)?([^\s>]+)>").expect("Invalid regex"));
+
+impl MicroblogMeta {
+    fn parse(&mut self, line: &str) {
+        let Some((key, value)) = line.split_once('=') else {
+            return;
+        };
+
+        let value = value.trim().to_string();
+        match key.trim() {
+            "nick" => self.nick = Some(value),
+            "url" => {
+                self.url.get_or_insert(value);
+            }
+            "description" => self.description = Some(value),
+            "avatar" => self.avatar = Some(value),
+            "follow" => {
+                if let Some((nick, url)) = value.split_once(char::is_whitespace) {
+                    self.follow.push((nick.to_string(), url.trim().to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl MicroblogEntry {
+    /// Parses a `timestamp<TAB>text` line of the feed published at `url`.
+    fn parse(line: &str, url: &str) -> Result<Self, chrono::ParseError> {
+        let mut parts = line.splitn(2, char::is_whitespace);
         let datetime_str = parts.next().unwrap_or("");
         let text = parts.next().unwrap_or("").trim_start();
 
-        let date = DateTime::parse_from_rfc3339(datetime_str)?.with_timezone(&Utc);
+        let created = DateTime::parse_from_rfc3339(datetime_str)?;
+
+        let reply_to = RE_SUBJECT
+            .captures(text)
+            .and_then(|caps| caps.get(1).or(caps.get(3)))
+            .map(|hash| hash.as_str().to_string());
 
         Ok(MicroblogEntry {
-            date,
+            date: created.with_timezone(&Utc),
             text: text.to_string(),
+            hash: twt_hash(url, created, text),
+            reply_to,
+            body: String::new(),
+        })
+    }
+
+    /// The text as it reads, without the reply subject and with mentions
+    /// shortened to `@nick`.
+    pub fn plain_text(&self) -> String {
+        let text = RE_SUBJECT.replace(&self.text, "");
+
+        RE_MENTION
+            .replace_all(text.trim_start(), |caps: &regex::Captures| {
+                let nick = caps.get(1).map_or(&caps[2], |nick| nick.as_str());
+                format!("@{nick}")
+            })
+            .into_owned()
+    }
+
+    /// Rewrites `@<nick url>` mentions and the `(#hash)` subject as links,
+    /// `local` resolving hashes of entries from the same feed to their pages.
+    fn to_markdown(&self, local: &HashMap<String, i64>) -> String {
+        let text = RE_SUBJECT.replace(&self.text, |caps: &regex::Captures| {
+            let hash = caps.get(1).or(caps.get(3)).map_or("", |hash| hash.as_str());
+            match (local.get(hash), caps.get(2)) {
+                (Some(timestamp), _) => format!("[#{hash}](/thoughts/{timestamp}/)"),
+                (None, Some(url)) => format!("[#{hash}](<{}>)", url.as_str()),
+                (None, None) => format!("`#{hash}`"),
+            }
+        });
+
+        RE_MENTION
+            .replace_all(&text, |caps: &regex::Captures| {
+                let url = &caps[2];
+                let nick = caps.get(1).map_or(url, |nick| nick.as_str());
+                format!("[@{nick}](<{url}>)")
+            })
+            .into_owned()
+    }
+}
+
+/// Computes the Yarn-style hash of an entry: the last 7 characters of the
+/// lowercase base32 encoded Blake2b-256 digest of its feed URL, timestamp and
+/// text.
+fn twt_hash(url: &str, created: DateTime<FixedOffset>, text: &str) -> String {
+    let created = created.to_rfc3339_opts(SecondsFormat::Secs, true);
+    let payload = format!("{url}\n{created}\n{text}");
+    let digest = Blake2b::<U32>::digest(payload.as_bytes());
+    let hash = BASE32_NOPAD.encode(&digest).to_lowercase();
+    hash[hash.len() - 7..].to_string()
+}
+
+impl Microblog {
+    /// Finds the entry with the given hash.
+    pub fn find(&self, hash: &str) -> Option<&MicroblogEntry> {
+        self.entries.iter().find(|entry| entry.hash == hash)
+    }
+
+    /// Collects the conversation the entry is part of, oldest first. Replies
+    /// are followed up to the first entry which is not a reply to another
+    /// entry in this feed, then every entry replying to the thread is added.
+    pub fn thread<'a>(&'a self, entry: &'a MicroblogEntry) -> Vec<&'a MicroblogEntry> {
+        let mut root = entry;
+        let mut seen = HashSet::from([root.hash.as_str()]);
+        while let Some(parent) = root.reply_to.as_deref().and_then(|hash| self.find(hash)) {
+            if !seen.insert(parent.hash.as_str()) {
+                break;
+            }
+            root = parent;
+        }
+
+        let mut thread = vec![root];
+        let mut hashes = HashSet::from([root.hash.as_str()]);
+        let mut i = 0;
+        while i < thread.len() {
+            let hash = thread[i].hash.as_str();
+            for reply in &self.entries {
+                if reply.reply_to.as_deref() == Some(hash) && hashes.insert(reply.hash.as_str()) {
+                    thread.push(reply);
+                }
+            }
+            i += 1;
+        }
+
+        thread.sort_by_key(|entry| entry.date);
+        thread
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum MicroblogError {
+    #[error(transparent)]
+    Date(#[from] chrono::ParseError),
+
+    #[error("Missing '# url = ...' header, entry hashes are computed from it")]
+    MissingUrl,
+}
+
+impl FromStr for Microblog {
+    type Err = MicroblogError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut meta = MicroblogMeta::default();
+        let mut lines = Vec::new();
+
+        for line in s.lines() {
+            let line = line.trim_start();
+            if let Some(comment) = line.strip_prefix('#') {
+                meta.parse(comment);
+            } else if !line.is_empty() {
+                lines.push(line);
+            }
+        }
+
+        let url = meta.url.as_deref().ok_or(MicroblogError::MissingUrl)?;
+        let mut entries = lines
+            .into_iter()
+            .map(|line| MicroblogEntry::parse(line, url))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let local = entries
+            .iter()
+            .map(|entry| (entry.hash.clone(), entry.date.timestamp()))
+            .collect::<HashMap<_, _>>();
+
+        for entry in &mut entries {
+            entry.body = entry.to_markdown(&local);
+        }
+
+        Ok(Microblog {
+            meta,
+            entries,
+            data: s.to_string(),
         })
     }
 }
@@ -57,6 +254,14 @@ pub struct Post {
     #[serde(default)]
     pub tags: Vec<String>,
     pub scripts: Option<Vec<String>>,
+    /// Citation style, either an archived style name (e.g. `apa`) or a
+    /// colocated `.csl` file.
+    pub csl: Option<String>,
+    /// Locale of the citation terms, e.g. `de-DE`, defaults to the one
+    /// preferred by the style.
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub bibliography: BibliographyMode,
 }
 
 // impl From<&WithFile<'_, Content<Post>>> for LinkDate {
@@ -112,6 +317,42 @@ pub struct Project {
 #[derive(Deserialize, Debug, Clone)]
 pub struct Wiki {
     pub title: String,
+    /// Other names the page can be linked by, e.g. `[[線形代数]]`.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Citation style, either an archived style name (e.g. `apa`) or a
+    /// colocated `.csl` file.
+    pub csl: Option<String>,
+    /// Locale of the citation terms, e.g. `de-DE`, defaults to the one
+    /// preferred by the style.
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub bibliography: BibliographyMode,
+}
+
+/// How Typst wiki pages are published. The PDF is always built, either as
+/// the page itself or as a download next to the rendered page.
+#[derive(Deserialize, clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TypstRender {
+    /// Embedded PDF viewer.
+    #[default]
+    Pdf,
+    /// Every page as an inline SVG. Faithful, but the text isn't searchable.
+    Svg,
+    /// Typst's experimental HTML export.
+    Html,
+}
+
+/// Which entries of a colocated `.bib` library end up in the bibliography.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BibliographyMode {
+    /// Only the works cited in the text.
+    #[default]
+    Cited,
+    /// Every entry in the library, cited or not.
+    All,
 }
 
 pub mod svelte {
@@ -139,3 +380,42 @@ mod isodate {
         Ok(dt.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Microblog;
+
+    #[test]
+    fn twtxt_header_hashes_and_replies() {
+        let microblog: Microblog = "\
+# nick   = hello
+# url    = https://example.com/twtxt.txt
+# url    = https://mirror.example.com/twtxt.txt
+# follow = bob https://bob.example.com/twtxt.txt
+
+2020-12-13T08:45:23+01:00\tHello World! 😊
+2020-12-13T09:00:00Z\t(#bxdggrq) Hi @<bob https://bob.example.com/twtxt.txt>
+"
+        .parse()
+        .unwrap();
+
+        let meta = &microblog.meta;
+        assert_eq!(meta.nick.as_deref(), Some("hello"));
+        assert_eq!(meta.url.as_deref(), Some("https://example.com/twtxt.txt"));
+        assert_eq!(meta.follow.len(), 1);
+
+        let [root, reply] = &microblog.entries[..] else {
+            panic!("expected two entries");
+        };
+        assert_eq!(root.hash, "bxdggrq");
+        assert_eq!(reply.reply_to.as_deref(), Some("bxdggrq"));
+        assert_eq!(
+            reply.body,
+            "[#bxdggrq](/thoughts/1607845523/) Hi [@bob](<https://bob.example.com/twtxt.txt>)"
+        );
+        assert_eq!(reply.plain_text(), "Hi @bob");
+        assert_eq!(microblog.thread(reply).len(), 2);
+
+        assert!("2020-12-13T09:00:00Z\tHello".parse::<Microblog>().is_err());
+    }
+}
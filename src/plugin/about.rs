@@ -8,6 +8,7 @@ use sequoia_openpgp::parse::Parse;
 
 use crate::md::Parsed;
 use crate::model::{Post, Pubkey};
+use crate::plugin::links::WikiLinks;
 use crate::props::PropsAbout;
 use crate::{Context, Global};
 
@@ -16,6 +17,7 @@ pub fn add_about(
     templates: One<TemplateEnv>,
     images: Many<Image>,
     styles: Many<Stylesheet>,
+    links: One<WikiLinks>,
 ) -> Result<One<Vec<Output>>, HauchiwaError> {
     let docs = config
         .load_documents::<Post>()
@@ -41,8 +43,8 @@ pub fn add_about(
 
     let handle = config
         .task()
-        .using((templates, docs, cert, images, styles))
-        .merge(|ctx, (templates, docs, cert, images, styles)| {
+        .using((templates, docs, cert, images, styles, links))
+        .merge(|ctx, (templates, docs, cert, images, styles, links)| {
             let document = docs.get("content/about/index.md")?;
             let pubkey_ident = cert.get("content/about/pubkey-ident.asc")?;
             let pubkey_email = cert.get("content/about/pubkey-email.asc")?;
@@ -52,8 +54,13 @@ pub fn add_about(
                 styles.get("styles/layouts/page.scss")?,
             ];
 
-            let parsed =
-                crate::md::parse(&document.text, &document.meta, None, Some(&images), None)?;
+            let parsed = crate::md::parse(
+                &document.text,
+                &document.meta,
+                Some(&links.resolver),
+                Some(&images),
+                None,
+            )?;
 
             let html = render(
                 ctx,
@@ -0,0 +1,47 @@
+use hauchiwa::error::HauchiwaError;
+use hauchiwa::loader::Document;
+use hauchiwa::prelude::*;
+
+use crate::Global;
+use crate::md::Parsed;
+use crate::model::{Microblog, Post, Slideshow, Wiki};
+
+/// Adds the site-wide `/feed.xml`, for readers who want to follow everything
+/// at once.
+pub fn add_feed(
+    config: &mut Blueprint<Global>,
+    posts: Many<Document<Post>>,
+    posts_parsed: Many<Option<Parsed>>,
+    slides: Many<Document<Slideshow>>,
+    slides_html: Many<String>,
+    twtxt: Many<Microblog>,
+    wiki: Many<Document<Wiki>>,
+) -> Result<One<Vec<Output>>, HauchiwaError> {
+    let handle = config
+        .task()
+        .name("feed")
+        .using((posts, posts_parsed, slides, slides_html, twtxt, wiki))
+        .merge(|ctx, deps| {
+            let (posts, posts_parsed, slides, slides_html, twtxt, wiki) = deps;
+
+            let posts = super::posts::published(&posts, &posts_parsed)
+                .into_iter()
+                .map(|(doc, parsed)| (doc, Some(parsed.html.clone())))
+                .collect::<Vec<_>>();
+            let slides = super::slides::rendered(&slides, &slides_html)
+                .into_iter()
+                .map(|(doc, html)| (doc, Some(html.to_string())))
+                .collect::<Vec<_>>();
+            let microblog = twtxt
+                .values()
+                .flat_map(|microblog| microblog.entries.iter().cloned())
+                .collect::<Vec<_>>();
+            let wiki = wiki.values().collect::<Vec<_>>();
+
+            Ok(vec![crate::rss::generate_site_feed(
+                ctx, &posts, &slides, &microblog, &wiki,
+            )])
+        });
+
+    Ok(handle)
+}
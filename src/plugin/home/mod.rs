@@ -5,6 +5,7 @@ use hauchiwa::prelude::*;
 use minijinja::Value;
 
 use crate::Context;
+use crate::plugin::links::WikiLinks;
 use crate::props::{PropsFooter, PropsHead, PropsHome, PropsNavItem, PropsNavbar};
 use crate::{Global, model::Home};
 
@@ -29,6 +30,7 @@ pub fn add_home(
     images: Many<Image>,
     styles: Many<Stylesheet>,
     scripts: Many<Script>,
+    links: One<WikiLinks>,
 ) -> Result<One<Output>, HauchiwaError> {
     let docs = config
         .load_documents::<Home>()
@@ -38,8 +40,8 @@ pub fn add_home(
 
     let task = config
         .task()
-        .using((templates, docs, images, styles, scripts))
-        .merge(|ctx, (templates, docs, images, styles, scripts)| {
+        .using((templates, docs, images, styles, scripts, links))
+        .merge(|ctx, (templates, docs, images, styles, scripts, links)| {
             let document = docs.get("content/index.md")?;
 
             let styles = &[
@@ -50,8 +52,13 @@ pub fn add_home(
 
             let scripts = &[scripts.get("scripts/kanji/main.ts")?];
 
-            let parsed =
-                crate::md::parse(&document.text, &document.meta, None, Some(&images), None)?;
+            let parsed = crate::md::parse(
+                &document.text,
+                &document.meta,
+                Some(&links.resolver),
+                Some(&images),
+                None,
+            )?;
 
             let html = render(ctx, templates, &parsed.html, styles, scripts)?;
 
@@ -80,6 +87,9 @@ pub(crate) fn render(
             styles: styles.iter().map(|s| s.path.to_string()).collect(),
             scripts: scripts.iter().map(|s| s.path.to_string()).collect(),
             refresh_script: ctx.env.get_refresh_script().map(Value::from_safe_string),
+            feeds: crate::rss::alternates(None),
+            description: None,
+            keywords: Vec::new(),
         },
         navbar: PropsNavbar {
             logotype_svg: Value::from_safe_string(LOGOTYPE_SVG.to_string()),
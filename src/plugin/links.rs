@@ -0,0 +1,108 @@
+use hauchiwa::loader::Document;
+use hauchiwa::output;
+use hauchiwa::prelude::*;
+
+use crate::Global;
+use crate::md::WikiLinkResolver;
+use crate::model::{Post, Project, Slideshow, Wiki};
+use crate::plugin::wiki::TypstPage;
+
+/// A page outside of the garden which links into it.
+pub struct ExternalLinks {
+    pub href: String,
+    pub title: String,
+    pub targets: Vec<String>,
+}
+
+/// Site-wide wiki link data, shared by every collection that renders Markdown.
+pub struct WikiLinks {
+    pub resolver: WikiLinkResolver,
+    /// Outbound links of posts, slides and projects.
+    pub external: Vec<ExternalLinks>,
+}
+
+pub fn add_wikilinks(
+    config: &mut Blueprint<Global>,
+    posts: Many<Document<Post>>,
+    wiki: Many<Document<Wiki>>,
+    typst: Many<TypstPage>,
+    projects: Many<Document<Project>>,
+    slides: Many<Document<Slideshow>>,
+) -> One<WikiLinks> {
+    config
+        .task()
+        .name("wikilinks")
+        .using((posts, wiki, typst, projects, slides))
+        .merge(|_, (posts, wiki, typst, projects, slides)| {
+            let posts = posts
+                .values()
+                .filter(|item| !item.matter.draft)
+                .collect::<Vec<_>>();
+
+            // projects with an external link don't have a page
+            let projects = projects
+                .values()
+                .filter(|item| item.matter.link.is_none())
+                .collect::<Vec<_>>();
+
+            let mut resolver = WikiLinkResolver::new();
+            for doc in wiki.values() {
+                resolver.add(doc);
+
+                let names = std::iter::once(&doc.matter.title).chain(&doc.matter.aliases);
+                resolver.add_aliases(&doc.meta.href, names.map(String::as_str));
+            }
+            // slides are split into separate sections, so their headings
+            // can't be linked to
+            for slide in slides.values() {
+                resolver.add_href(&slide.meta.href);
+            }
+            for post in &posts {
+                resolver.add(post);
+            }
+            for project in &projects {
+                resolver.add(project);
+            }
+            for page in typst.values() {
+                let href = output::source_to_href(&page.path, Some("content"));
+                resolver.add_href(&href);
+                resolver.add_aliases(&href, [page.title.as_str()]);
+            }
+
+            let mut external = Vec::new();
+
+            let pages = Iterator::chain(
+                posts
+                    .iter()
+                    .map(|doc| (&doc.meta.href, &doc.matter.title, &doc.text)),
+                slides
+                    .values()
+                    .map(|doc| (&doc.meta.href, &doc.matter.title, &doc.text)),
+            )
+            .chain(
+                projects
+                    .iter()
+                    .filter(|doc| doc.meta.path.extension() != Some("html"))
+                    .map(|doc| (&doc.meta.href, &doc.matter.title, &doc.text)),
+            );
+
+            for (href, title, text) in pages {
+                // Broken links are reported once the document itself is rendered
+                let targets = crate::md::wikilinks(text)
+                    .iter()
+                    .filter_map(|link| resolver.resolve(link).ok())
+                    .map(|href| crate::md::strip_fragment(&href).to_string())
+                    .collect::<Vec<_>>();
+
+                if !targets.is_empty() {
+                    external.push(ExternalLinks {
+                        href: href.clone(),
+                        title: title.clone(),
+                        targets,
+                    });
+                }
+            }
+
+            Ok(WikiLinks { resolver, external })
+        })
+}
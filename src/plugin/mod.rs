@@ -1,5 +1,7 @@
 pub mod about;
+pub mod feed;
 pub mod home;
+pub mod links;
 pub mod posts;
 pub mod projects;
 pub mod slides;
@@ -7,20 +9,22 @@ pub mod tags;
 pub mod twtxt;
 pub mod wiki;
 
-use std::{cmp::Reverse, collections::HashMap};
+use std::{borrow::Cow, cmp::Reverse, collections::HashMap};
 
 use camino::Utf8Path;
 use chrono::Datelike as _;
 use hauchiwa::{
+    Tracker,
     error::RuntimeError,
-    loader::{Script, Stylesheet, TemplateEnv},
+    loader::{Script, Stylesheet, TemplateEnv, generic::DocumentMeta},
 };
+use hayagriva::citationberg::IndependentStyle;
 use minijinja::Value;
 
 use crate::props::{
     PropsFooter, PropsHead, PropsList, PropsListGroup, PropsListItem, PropsNavItem, PropsNavbar,
 };
-use crate::{Context, LinkDate};
+use crate::{Context, Csl, LinkDate};
 
 const LOGOTYPE_SVG: &str = include_str!("../assets/logotype.svg");
 const ICON_RSS: &str = include_str!("../assets/rss.svg");
@@ -71,15 +75,44 @@ pub(crate) fn make_props_head(
         styles: styles.iter().map(|s| s.path.to_string()).collect(),
         scripts: scripts.iter().map(|s| s.path.to_string()).collect(),
         refresh_script: ctx.env.get_refresh_script().map(Value::from_safe_string),
+        feeds: crate::rss::alternates(None),
+        description: None,
+        keywords: Vec::new(),
     })
 }
 
+/// Resolves the `csl` frontmatter field of a document, which either points to a
+/// colocated `.csl` file or names one of the styles archived in hayagriva.
+pub(crate) fn get_citation_style<'a>(
+    csl: Option<&str>,
+    meta: &DocumentMeta,
+    styles: &'a Tracker<Csl>,
+) -> Result<Cow<'a, IndependentStyle>, RuntimeError> {
+    let Some(name) = csl else {
+        return Ok(Cow::Borrowed(&crate::md::STYLE));
+    };
+
+    if name.ends_with(".csl") {
+        let path = meta.resolve(name);
+        return Ok(Cow::Borrowed(&styles.get(path.as_str())?.style));
+    }
+
+    crate::md::archived_style(name)
+        .map(Cow::Owned)
+        .ok_or_else(|| {
+            RuntimeError::msg(format!(
+                "Unknown citation style '{name}' in '{}'",
+                meta.path
+            ))
+        })
+}
+
 pub(crate) fn to_list(
     ctx: &Context,
     templates: &TemplateEnv,
     list: Vec<LinkDate>,
     title: String,
-    rss: &'static str,
+    feed: (&str, &str),
     styles: &[&Stylesheet],
 ) -> Result<String, RuntimeError> {
     let mut groups = HashMap::<i32, Vec<_>>::new();
@@ -99,11 +132,14 @@ pub(crate) fn to_list(
     groups.sort_by_key(|item| Reverse(item.0));
 
     let props = PropsList {
-        head: make_props_head(ctx, title.clone(), styles, &[])?,
+        head: PropsHead {
+            feeds: crate::rss::alternates(Some(feed)),
+            ..make_props_head(ctx, title.clone(), styles, &[])?
+        },
         navbar: make_props_navbar(),
         footer: make_props_footer(ctx),
         title,
-        rss,
+        rss: format!("/{}/rss.xml", feed.0),
         icon_rss: Value::from_safe_string(ICON_RSS.to_string()),
         groups: groups
             .into_iter()
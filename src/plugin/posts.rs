@@ -2,46 +2,102 @@ use camino::Utf8Path;
 use hauchiwa::error::{HauchiwaError, RuntimeError};
 use hauchiwa::git::GitHistory;
 use hauchiwa::loader::{Document, Image, Script, Stylesheet, TemplateEnv};
-use hauchiwa::prelude::*;
+use hauchiwa::{Tracker, prelude::*};
 use hypertext::prelude::*;
 use minijinja::Value;
 
 use crate::md::Parsed;
 use crate::model::Post;
-use crate::props::{PropsBibliography, PropsPost, PropsPostMeta, PropsPostUpdated};
-use crate::{Bibtex, Context, Global, Link, LinkDate};
+use crate::plugin::links::WikiLinks;
+use crate::props::{PropsBibliography, PropsHead, PropsPost, PropsPostMeta, PropsPostUpdated};
+use crate::{Bibtex, Context, Csl, Global, Link, LinkDate};
 
 use super::to_list;
 
-type PostsOutput = (Many<Document<Post>>, One<Vec<Output>>);
+pub fn load_posts(config: &mut Blueprint<Global>) -> Result<Many<Document<Post>>, HauchiwaError> {
+    let docs = config
+        .load_documents::<Post>()
+        .glob("content/posts/**/*.md")?
+        .base("content")
+        .register();
+
+    Ok(docs)
+}
+
+/// Parses the published posts, their HTML is shared by the pages and by every
+/// feed which includes them. Drafts are left unparsed.
+pub fn parse_posts(
+    config: &mut Blueprint<Global>,
+    docs: Many<Document<Post>>,
+    images: Many<Image>,
+    bibtex: Many<Bibtex>,
+    csl: Many<Csl>,
+    links: One<WikiLinks>,
+) -> Many<Option<Parsed>> {
+    config
+        .task()
+        .name("posts-parse")
+        .each(docs)
+        .using((images, bibtex, csl, links))
+        .map(|_, document, (images, bibtex, csl, links)| {
+            if document.matter.draft {
+                return Ok(None);
+            }
+
+            let bibtex = bibtex.glob(&document.meta.assets("*.bib"))?.next();
+
+            let style =
+                super::get_citation_style(document.matter.csl.as_deref(), &document.meta, &csl)?;
+
+            let parsed = crate::md::parse(
+                &document.text,
+                &document.meta,
+                Some(&links.resolver),
+                Some(&images),
+                bibtex.map(|(_, library)| crate::md::Library {
+                    data: &library.data,
+                    style: &style,
+                    mode: document.matter.bibliography,
+                    locale: document.matter.locale.as_deref(),
+                }),
+            )?;
+
+            Ok(Some(parsed))
+        })
+}
+
+/// Pairs each published post with its parsed content.
+pub fn published<'a>(
+    docs: &'a Tracker<Document<Post>>,
+    parsed: &'a Tracker<Option<Parsed>>,
+) -> Vec<(&'a Document<Post>, &'a Parsed)> {
+    docs.iter()
+        .filter_map(|(key, doc)| Some((doc, parsed.get(key).ok()?.as_ref()?)))
+        .collect()
+}
 
 pub fn add_posts(
     config: &mut Blueprint<Global>,
     templates: One<TemplateEnv>,
-    images: Many<Image>,
+    docs: Many<Document<Post>>,
+    parsed: Many<Option<Parsed>>,
     styles: Many<Stylesheet>,
     scripts: Many<Script>,
     bibtex: Many<Bibtex>,
-) -> Result<PostsOutput, HauchiwaError> {
-    let docs = config
-        .load_documents::<Post>()
-        .glob("content/posts/**/*.md")?
-        .base("content")
-        .register();
-
+) -> Result<One<Vec<Output>>, HauchiwaError> {
     let pages = config
         .task()
-        .using((templates, docs, images, styles, scripts, bibtex))
-        .merge(|ctx, (templates, docs, images, styles, scripts, bibtex)| {
+        .using((templates, docs, parsed, styles, scripts, bibtex))
+        .merge(|ctx, deps| {
+            let (templates, docs, parsed, styles, scripts, bibtex) = deps;
+
             let mut pages = vec![];
+            let mut feed = vec![];
 
-            let documents = docs
-                .values()
-                .filter(|item| !item.matter.draft)
-                .collect::<Vec<_>>();
+            let documents = published(&docs, &parsed);
 
             // render the posts
-            for document in &documents {
+            for (document, parsed) in &documents {
                 let bibtex = bibtex.glob(&document.meta.assets("*.bib"))?.next();
 
                 let styles = &[
@@ -64,13 +120,7 @@ pub fn add_posts(
                     }
                 }
 
-                let parsed = crate::md::parse(
-                    &document.text,
-                    &document.meta,
-                    None,
-                    Some(&images),
-                    bibtex.map(|(_, library)| &library.data),
-                )?;
+                feed.push((*document, Some(parsed.html.clone())));
 
                 let buffer = render(RenderPost {
                     ctx,
@@ -103,7 +153,7 @@ pub fn add_posts(
                     templates,
                     documents
                         .iter()
-                        .map(|item| LinkDate {
+                        .map(|(item, _)| LinkDate {
                             link: Link {
                                 path: camino::Utf8PathBuf::from(&item.meta.href),
                                 name: item.matter.title.clone(),
@@ -113,7 +163,7 @@ pub fn add_posts(
                         })
                         .collect(),
                     "Posts".into(),
-                    "/posts/rss.xml",
+                    crate::rss::FEED_POSTS,
                     styles,
                 )?;
 
@@ -121,24 +171,24 @@ pub fn add_posts(
             }
 
             {
-                pages.push(crate::rss::generate_feed(
-                    &documents,
-                    "posts",
-                    "Kamoshi.org Posts",
-                ));
+                pages.extend(crate::rss::generate_feed(
+                    ctx,
+                    &feed,
+                    crate::rss::FEED_POSTS,
+                )?);
             }
 
             Ok(pages)
         });
 
-    Ok((docs, pages))
+    Ok(pages)
 }
 
 pub struct RenderPost<'a> {
     pub ctx: &'a Context<'a>,
     pub templates: &'a TemplateEnv,
     pub meta: &'a Post,
-    pub parsed: Parsed,
+    pub parsed: &'a Parsed,
     pub info: Option<&'a GitHistory>,
     pub library_path: Option<&'a Utf8Path>,
     pub tags: &'a [String],
@@ -161,8 +211,8 @@ pub fn render(args: RenderPost) -> Result<String, RuntimeError> {
 
     let outline_html = parsed.outline.render().into_inner();
 
-    let bibliography = parsed.bibliography.map(|bib| PropsBibliography {
-        items: bib.into_iter().map(Value::from_safe_string).collect(),
+    let bibliography = parsed.bibliography.as_ref().map(|bib| PropsBibliography {
+        items: bib.iter().cloned().map(Value::from_safe_string).collect(),
         library_path: library_path.map(|p| p.to_string()),
     });
 
@@ -177,12 +227,15 @@ pub fn render(args: RenderPost) -> Result<String, RuntimeError> {
     });
 
     let props = PropsPost {
-        head: super::make_props_head(ctx, meta.title.clone(), styles, scripts)?,
+        head: PropsHead {
+            feeds: crate::rss::alternates(Some(crate::rss::FEED_POSTS)),
+            ..super::make_props_head(ctx, meta.title.clone(), styles, scripts)?
+        },
         navbar: super::make_props_navbar(),
         footer: super::make_props_footer(ctx),
         title: meta.title.clone(),
         outline: Value::from_safe_string(outline_html),
-        content: Value::from_safe_string(parsed.html),
+        content: Value::from_safe_string(parsed.html.clone()),
         bibliography,
         metadata: PropsPostMeta {
             date_added: meta.date.format("%Y, %B %d").to_string(),
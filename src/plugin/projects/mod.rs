@@ -2,14 +2,15 @@ mod radicals;
 
 use camino::Utf8PathBuf;
 use hauchiwa::error::{HauchiwaError, RuntimeError};
-use hauchiwa::loader::{Script, Stylesheet, TemplateEnv};
+use hauchiwa::loader::{Document, Script, Stylesheet, TemplateEnv};
 use hauchiwa::prelude::*;
 use hypertext::prelude::*;
 use minijinja::Value;
 
 use crate::md::Parsed;
 use crate::model::Project;
-use crate::props::{PropsProjectPage, PropsProjectTile, PropsProjects, PropsRawPage};
+use crate::plugin::links::WikiLinks;
+use crate::props::{PropsHead, PropsProjectPage, PropsProjectTile, PropsProjects, PropsRawPage};
 use crate::{Context, Global};
 
 pub struct ProjectView<'a> {
@@ -20,12 +21,9 @@ pub struct ProjectView<'a> {
     pub external: bool,
 }
 
-pub fn add_projects(
+pub fn load_projects(
     config: &mut Blueprint<Global>,
-    templates: One<TemplateEnv>,
-    styles: Many<Stylesheet>,
-    scripts: Many<Script>,
-) -> Result<One<Vec<Output>>, HauchiwaError> {
+) -> Result<Many<Document<Project>>, HauchiwaError> {
     let docs = config
         .load_documents::<Project>()
         .glob("content/projects/**/*.md")?
@@ -33,12 +31,25 @@ pub fn add_projects(
         .base("content")
         .register();
 
+    Ok(docs)
+}
+
+pub fn add_projects(
+    config: &mut Blueprint<Global>,
+    templates: One<TemplateEnv>,
+    docs: Many<Document<Project>>,
+    styles: Many<Stylesheet>,
+    scripts: Many<Script>,
+    links: One<WikiLinks>,
+) -> Result<One<Vec<Output>>, HauchiwaError> {
     let page_radicals = radicals::build(config, templates, styles)?;
 
     let task = config
         .task()
-        .using((templates, docs, styles, scripts, page_radicals))
-        .merge(|ctx, (templates, docs, styles, scripts, page_radicals)| {
+        .using((templates, docs, styles, scripts, page_radicals, links))
+        .merge(|ctx, deps| {
+            let (templates, docs, styles, scripts, page_radicals, links) = deps;
+
             let styles_list = &[
                 styles.get("styles/styles.scss")?,
                 styles.get("styles/layouts/projects.scss")?,
@@ -46,8 +57,11 @@ pub fn add_projects(
 
             let mut project_views: Vec<ProjectView> = vec![];
             let mut pages = vec![];
+            let mut feed = vec![];
 
             for doc in docs.values() {
+                let mut content = None;
+
                 let (link, external) = match &doc.matter.link {
                     Some(url) => (url.clone(), true),
                     None => {
@@ -76,7 +90,14 @@ pub fn add_projects(
                                 doc.matter.hide_footer.unwrap_or(false),
                             )?
                         } else {
-                            let parsed = crate::md::parse(&doc.text, &doc.meta, None, None, None)?;
+                            let parsed = crate::md::parse(
+                                &doc.text,
+                                &doc.meta,
+                                Some(&links.resolver),
+                                None,
+                                None,
+                            )?;
+                            content = Some(parsed.html.clone());
                             render_page(
                                 ctx,
                                 templates,
@@ -92,6 +113,7 @@ pub fn add_projects(
                         (href, false)
                     }
                 };
+                feed.push((doc, content));
                 project_views.push(ProjectView {
                     title: &doc.matter.title,
                     tech: doc.matter.tech.clone(),
@@ -117,12 +139,11 @@ pub fn add_projects(
             });
 
             {
-                let docs_vec = docs.values().collect::<Vec<_>>();
-                pages.push(crate::rss::generate_feed(
-                    &docs_vec,
-                    "projects",
-                    "Kamoshi.org Projects",
-                ));
+                pages.extend(crate::rss::generate_feed(
+                    ctx,
+                    &feed,
+                    crate::rss::FEED_PROJECTS,
+                )?);
             }
 
             {
@@ -145,7 +166,10 @@ pub fn render_list(
     projects.sort_unstable_by(|a, b| a.title.cmp(b.title));
 
     let props = PropsProjects {
-        head: super::make_props_head(ctx, "Projects".to_string(), styles, &[])?,
+        head: PropsHead {
+            feeds: crate::rss::alternates(Some(crate::rss::FEED_PROJECTS)),
+            ..super::make_props_head(ctx, "Projects".to_string(), styles, &[])?
+        },
         navbar: super::make_props_navbar(),
         footer: super::make_props_footer(ctx),
         projects: projects
@@ -175,7 +199,10 @@ pub fn render_page(
     let outline_html = parsed.outline.render().into_inner();
 
     let props = PropsProjectPage {
-        head: super::make_props_head(ctx, title.to_string(), styles, scripts)?,
+        head: PropsHead {
+            feeds: crate::rss::alternates(Some(crate::rss::FEED_PROJECTS)),
+            ..super::make_props_head(ctx, title.to_string(), styles, scripts)?
+        },
         navbar: super::make_props_navbar(),
         footer: super::make_props_footer(ctx),
         outline: Value::from_safe_string(outline_html),
@@ -196,7 +223,10 @@ pub fn render_raw_page(
     hide_footer: bool,
 ) -> Result<String, RuntimeError> {
     let props = PropsRawPage {
-        head: super::make_props_head(ctx, title.to_string(), styles, scripts)?,
+        head: PropsHead {
+            feeds: crate::rss::alternates(Some(crate::rss::FEED_PROJECTS)),
+            ..super::make_props_head(ctx, title.to_string(), styles, scripts)?
+        },
         navbar: super::make_props_navbar(),
         footer: super::make_props_footer(ctx),
         content: Value::from_safe_string(html.to_string()),
@@ -3,22 +3,20 @@ use std::fmt::Write as _;
 use camino::Utf8PathBuf;
 use hauchiwa::error::{HauchiwaError, RuntimeError};
 use hauchiwa::loader::generic::DocumentMeta;
-use hauchiwa::loader::{Image, Script, Stylesheet, TemplateEnv};
+use hauchiwa::loader::{Document, Image, Script, Stylesheet, TemplateEnv};
 use hauchiwa::{Tracker, prelude::*};
 use minijinja::Value;
 
+use crate::md::WikiLinkResolver;
 use crate::model::Slideshow;
+use crate::plugin::links::WikiLinks;
 use crate::plugin::to_list;
-use crate::props::PropsSlideshow;
+use crate::props::{PropsHead, PropsSlideshow};
 use crate::{Context, Global, Link, LinkDate};
 
-pub fn add_slides(
+pub fn load_slides(
     config: &mut Blueprint<Global>,
-    templates: One<TemplateEnv>,
-    images: Many<Image>,
-    styles: Many<Stylesheet>,
-    scripts: Many<Script>,
-) -> Result<One<Vec<Output>>, HauchiwaError> {
+) -> Result<Many<Document<Slideshow>>, HauchiwaError> {
     let md = config
         .load_documents::<Slideshow>()
         .glob("content/slides/**/*.md")?
@@ -26,13 +24,59 @@ pub fn add_slides(
         .base("content")
         .register();
 
+    Ok(md)
+}
+
+/// Renders the slideshows to HTML, shared by their pages and the site feed.
+pub fn parse_slides(
+    config: &mut Blueprint<Global>,
+    md: Many<Document<Slideshow>>,
+    images: Many<Image>,
+    links: One<WikiLinks>,
+) -> Many<String> {
+    config
+        .task()
+        .name("slides-parse")
+        .each(md)
+        .using((images, links))
+        .map(|_, document, (images, links)| {
+            let text = parse(
+                &document.text,
+                &document.meta,
+                Some(&links.resolver),
+                None,
+                Some(&images),
+            )?;
+
+            Ok(text)
+        })
+}
+
+/// Pairs each slideshow with its rendered slides.
+pub fn rendered<'a>(
+    md: &'a Tracker<Document<Slideshow>>,
+    html: &'a Tracker<String>,
+) -> Vec<(&'a Document<Slideshow>, &'a str)> {
+    md.iter()
+        .filter_map(|(key, doc)| Some((doc, html.get(key).ok()?.as_str())))
+        .collect()
+}
+
+pub fn add_slides(
+    config: &mut Blueprint<Global>,
+    templates: One<TemplateEnv>,
+    md: Many<Document<Slideshow>>,
+    html: Many<String>,
+    styles: Many<Stylesheet>,
+    scripts: Many<Script>,
+) -> Result<One<Vec<Output>>, HauchiwaError> {
     let handle = config
         .task()
-        .using((templates, md, images, styles, scripts))
-        .merge(|ctx, (templates, md, images, styles, scripts)| {
+        .using((templates, md, html, styles, scripts))
+        .merge(|ctx, (templates, md, html, styles, scripts)| {
             let mut pages = vec![];
 
-            let documents = md.values().collect::<Vec<_>>();
+            let documents = rendered(&md, &html);
 
             {
                 let styles = &[
@@ -42,11 +86,10 @@ pub fn add_slides(
 
                 let scripts = &[scripts.get("scripts/slides/main.ts")?];
 
-                for document in &documents {
-                    let text = parse(&document.text, &document.meta, None, Some(&images))?;
-                    let html = render(ctx, templates, &document.matter, &text, styles, scripts)?;
+                for (document, text) in &documents {
+                    let html = render(ctx, templates, &document.matter, text, styles, scripts)?;
 
-                    pages.push(Output::to(document).html(html)?);
+                    pages.push(Output::to(*document).html(html)?);
                 }
             }
 
@@ -59,7 +102,7 @@ pub fn add_slides(
 
                 let data = documents
                     .iter()
-                    .map(|item| LinkDate {
+                    .map(|(item, _)| LinkDate {
                         link: Link {
                             path: Utf8PathBuf::from(&item.meta.href),
                             name: item.matter.title.clone(),
@@ -74,7 +117,7 @@ pub fn add_slides(
                     templates,
                     data,
                     "Slideshows".into(),
-                    "/slides/rss.xml",
+                    crate::rss::FEED_SLIDES,
                     styles,
                 )?;
 
@@ -83,11 +126,16 @@ pub fn add_slides(
 
             // render feed
             {
-                pages.push(crate::rss::generate_feed(
-                    &documents,
-                    "slides",
-                    "Kamoshi.org Slides",
-                ));
+                let feed = documents
+                    .iter()
+                    .map(|(document, text)| (*document, Some(text.to_string())))
+                    .collect::<Vec<_>>();
+
+                pages.extend(crate::rss::generate_feed(
+                    ctx,
+                    &feed,
+                    crate::rss::FEED_SLIDES,
+                )?);
             }
 
             Ok(pages)
@@ -99,7 +147,8 @@ pub fn add_slides(
 pub fn parse(
     text: &str,
     meta: &DocumentMeta,
-    library: Option<&hayagriva::Library>,
+    resolver: Option<&WikiLinkResolver>,
+    library: Option<crate::md::Library>,
     images: Option<&Tracker<Image>>,
 ) -> Result<String, RuntimeError> {
     let mut buff = String::new();
@@ -108,7 +157,7 @@ pub fn parse(
         buff.push_str("<section>");
 
         for slide in stack.split("\n---\n") {
-            let article = crate::md::parse(slide, meta, None, images, library)?;
+            let article = crate::md::parse(slide, meta, resolver, images, library)?;
             write!(buff, "<section>{}</section>", article.html)?;
         }
 
@@ -127,7 +176,10 @@ pub fn render(
     scripts: &[&Script],
 ) -> Result<String, RuntimeError> {
     let props = PropsSlideshow {
-        head: super::make_props_head(ctx, fm.title.clone(), styles, scripts)?,
+        head: PropsHead {
+            feeds: crate::rss::alternates(Some(crate::rss::FEED_SLIDES)),
+            ..super::make_props_head(ctx, fm.title.clone(), styles, scripts)?
+        },
         slides: Value::from_safe_string(slides.to_string()),
     };
 
@@ -8,62 +8,75 @@ use chrono::Datelike;
 use hauchiwa::error::{HauchiwaError, RuntimeError};
 use hauchiwa::loader::{Document, Stylesheet, TemplateEnv};
 use hauchiwa::prelude::*;
+use minijinja::Value;
 
-use crate::props::{PropsListGroup, PropsListItem, PropsTag, PropsTagCloud, PropsTagCloudEntry};
+use crate::md::Parsed;
+use crate::props::{
+    PropsHead, PropsListGroup, PropsListItem, PropsTag, PropsTagCloud, PropsTagCloudEntry,
+};
 use crate::{Context, Global, Link, LinkDate, model::Post};
 
 pub fn add_tags(
     config: &mut Blueprint<Global>,
     templates: One<TemplateEnv>,
     posts: Many<Document<Post>>,
+    parsed: Many<Option<Parsed>>,
     styles: Many<Stylesheet>,
 ) -> Result<One<Vec<Output>>, HauchiwaError> {
-    let handle =
-        config
-            .task()
-            .using((templates, posts, styles))
-            .merge(|ctx, (templates, posts, styles)| {
-                let styles = &[
-                    styles.get("styles/styles.scss")?,
-                    styles.get("styles/layouts/list.scss")?,
-                    styles.get("styles/layouts/tags.scss")?,
-                ];
-
-                let posts = posts
-                    .values()
-                    .filter(|item| !item.matter.draft)
-                    .collect::<Vec<_>>();
-
-                let mut tag_map: BTreeMap<String, Vec<LinkDate>> = BTreeMap::new();
-
-                for post in &posts {
-                    for tag in &post.matter.tags {
-                        tag_map.entry(tag.clone()).or_default().push(LinkDate {
-                            link: Link {
-                                path: Utf8PathBuf::from(&post.meta.href),
-                                name: post.matter.title.clone(),
-                                desc: post.matter.desc.clone(),
-                            },
-                            date: post.matter.date,
-                        });
-                    }
+    let handle = config
+        .task()
+        .using((templates, posts, parsed, styles))
+        .merge(|ctx, (templates, posts, parsed, styles)| {
+            let styles = &[
+                styles.get("styles/styles.scss")?,
+                styles.get("styles/layouts/list.scss")?,
+                styles.get("styles/layouts/tags.scss")?,
+            ];
+
+            let posts = super::posts::published(&posts, &parsed);
+
+            let mut tag_map: BTreeMap<String, Vec<LinkDate>> = BTreeMap::new();
+            let mut tag_docs = HashMap::<&str, Vec<_>>::new();
+
+            for (post, parsed) in &posts {
+                for tag in &post.matter.tags {
+                    let html = Some(parsed.html.clone());
+                    tag_docs.entry(tag).or_default().push((*post, html));
+                    tag_map.entry(tag.clone()).or_default().push(LinkDate {
+                        link: Link {
+                            path: Utf8PathBuf::from(&post.meta.href),
+                            name: post.matter.title.clone(),
+                            desc: post.matter.desc.clone(),
+                        },
+                        date: post.matter.date,
+                    });
                 }
+            }
 
-                let mut pages = Vec::new();
+            let mut pages = Vec::new();
 
-                for (tag, links) in &tag_map {
-                    let path = format!("tags/{tag}/index.html");
-                    let html = render_tag(ctx, templates, &group(links), tag.to_owned(), styles)?;
-                    pages.push(Output::html(path, html));
-                }
+            for (tag, links) in &tag_map {
+                let slug = format!("tags/{tag}");
+                let title = format!("Kamoshi.org #{tag}");
+                let feed = (slug.as_str(), title.as_str());
+                let html = render_tag(ctx, templates, &group(links), tag.to_owned(), feed, styles)?;
+                pages.push(Output::html(format!("{slug}/index.html"), html));
+
+                let docs = tag_docs.get(tag.as_str()).map(Vec::as_slice);
+                pages.extend(crate::rss::generate_feed(
+                    ctx,
+                    docs.unwrap_or_default(),
+                    feed,
+                )?);
+            }
 
-                pages.push(Output::html(
-                    "tags",
-                    tag_cloud(ctx, templates, &tag_map, "Tags", styles)?,
-                ));
+            pages.push(Output::html(
+                "tags",
+                tag_cloud(ctx, templates, &tag_map, "Tags", styles)?,
+            ));
 
-                Ok(pages)
-            });
+            Ok(pages)
+        });
 
     Ok(handle)
 }
@@ -92,12 +105,18 @@ pub fn render_tag(
     templates: &TemplateEnv,
     links: &[(i32, Vec<&LinkDate>)],
     title: String,
+    feed: (&str, &str),
     styles: &[&Stylesheet],
 ) -> Result<String, RuntimeError> {
     let props = PropsTag {
-        head: super::make_props_head(ctx, title.clone(), styles, &[])?,
+        head: PropsHead {
+            feeds: crate::rss::alternates(Some(feed)),
+            ..super::make_props_head(ctx, title.clone(), styles, &[])?
+        },
         navbar: super::make_props_navbar(),
         footer: super::make_props_footer(ctx),
+        rss: format!("/{}/rss.xml", feed.0),
+        icon_rss: Value::from_safe_string(super::ICON_RSS.to_string()),
         title,
         groups: links
             .iter()
@@ -6,34 +6,29 @@ use hauchiwa::prelude::*;
 use minijinja::Value;
 
 use crate::model::{Microblog, MicroblogEntry};
-use crate::props::{PropsMicroblogEntry, PropsThought, PropsThoughts};
+use crate::props::{
+    PropsHead, PropsMicroblogAuthor, PropsMicroblogEntry, PropsThought, PropsThoughts,
+};
 use crate::{Context, Global};
 
-pub fn add_twtxt(
-    config: &mut Blueprint<Global>,
-    templates: One<TemplateEnv>,
-    styles: Many<Stylesheet>,
-) -> Result<Many<Output>, HauchiwaError> {
+pub fn load_twtxt(config: &mut Blueprint<Global>) -> Result<Many<Microblog>, HauchiwaError> {
     let twtxt = config.task().glob("content/twtxt.txt")?.map(|_, _, file| {
         let data = file.read()?;
         let data = String::from_utf8_lossy(&data);
 
-        let entries = data
-            .lines()
-            .filter(|line| {
-                let line = line.trim_start();
-                !line.is_empty() && !line.starts_with('#')
-            })
-            .map(str::parse::<MicroblogEntry>)
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|err| RuntimeError::msg(format!("Failed to parse '{}': {err}", file.path)))?;
-
-        Ok(Microblog {
-            entries,
-            data: data.to_string(),
-        })
+        data.parse::<Microblog>()
+            .map_err(|err| RuntimeError::msg(format!("Failed to parse '{}': {err}", file.path)))
     });
 
+    Ok(twtxt)
+}
+
+pub fn add_twtxt(
+    config: &mut Blueprint<Global>,
+    templates: One<TemplateEnv>,
+    twtxt: Many<Microblog>,
+    styles: Many<Stylesheet>,
+) -> Result<Many<Output>, HauchiwaError> {
     let handle = config.task().using((templates, twtxt, styles)).spread(
         |ctx, (templates, twtxt, styles)| {
             let styles = &[
@@ -53,13 +48,23 @@ pub fn add_twtxt(
             ];
 
             for entry in &data.entries {
-                let html = render_entry(ctx, templates, entry, styles)?;
+                let html = render_entry(ctx, templates, data, entry, styles)?;
                 let date = entry.date.timestamp();
 
                 let path = format!("thoughts/{date}");
                 pages.push((path.clone(), Output::html(path, html)));
             }
 
+            let feed = data
+                .entries
+                .iter()
+                .map(|entry| (entry, None))
+                .collect::<Vec<_>>();
+
+            for output in crate::rss::generate_feed(ctx, &feed, crate::rss::FEED_THOUGHTS)? {
+                pages.push((output.path.to_string(), output));
+            }
+
             Ok(pages)
         },
     );
@@ -67,13 +72,33 @@ pub fn add_twtxt(
     Ok(handle)
 }
 
+/// Renders the entry with its mentions and reply subject linked.
+pub(crate) fn render_body(entry: &MicroblogEntry) -> String {
+    comrak::markdown_to_html(&entry.body, &comrak::Options::default())
+}
+
 fn make_entry_props(entry: &MicroblogEntry) -> PropsMicroblogEntry {
-    let body = comrak::markdown_to_html(&entry.text, &comrak::Options::default());
     PropsMicroblogEntry {
-        body: Value::from_safe_string(body),
+        body: Value::from_safe_string(render_body(entry)),
         date_iso: entry.date.to_rfc3339(),
         date_display: entry.date.format("%b %d").to_string(),
         timestamp: entry.date.timestamp(),
+        hash: entry.hash.clone(),
+    }
+}
+
+fn make_author_props(microblog: &Microblog) -> PropsMicroblogAuthor {
+    PropsMicroblogAuthor {
+        nick: microblog
+            .meta
+            .nick
+            .clone()
+            .unwrap_or_else(|| "kamov".into()),
+        avatar: microblog
+            .meta
+            .avatar
+            .clone()
+            .unwrap_or_else(|| "/aya_shades.png".into()),
     }
 }
 
@@ -87,9 +112,13 @@ pub fn render(
     entries.sort_by_key(|entry| Reverse(entry.date));
 
     let props = PropsThoughts {
-        head: super::make_props_head(ctx, "microblog".to_string(), styles, &[])?,
+        head: PropsHead {
+            feeds: crate::rss::alternates(Some(crate::rss::FEED_THOUGHTS)),
+            ..super::make_props_head(ctx, "microblog".to_string(), styles, &[])?
+        },
         navbar: super::make_props_navbar(),
         footer: super::make_props_footer(ctx),
+        author: make_author_props(microblog),
         entries: entries.iter().map(make_entry_props).collect(),
     };
 
@@ -100,14 +129,24 @@ pub fn render(
 pub fn render_entry(
     ctx: &Context,
     templates: &TemplateEnv,
+    microblog: &Microblog,
     entry: &MicroblogEntry,
     styles: &[&Stylesheet],
 ) -> Result<String, RuntimeError> {
     let props = PropsThought {
-        head: super::make_props_head(ctx, "microblog".to_string(), styles, &[])?,
+        head: PropsHead {
+            feeds: crate::rss::alternates(Some(crate::rss::FEED_THOUGHTS)),
+            ..super::make_props_head(ctx, "microblog".to_string(), styles, &[])?
+        },
         navbar: super::make_props_navbar(),
         footer: super::make_props_footer(ctx),
+        author: make_author_props(microblog),
         entry: make_entry_props(entry),
+        thread: microblog
+            .thread(entry)
+            .into_iter()
+            .map(make_entry_props)
+            .collect(),
     };
 
     let tmpl = templates.get_template("thought.jinja")?;
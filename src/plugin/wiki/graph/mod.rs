@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use blake2::{Blake2b, Digest, digest::consts::U32};
+use data_encoding::HEXLOWER;
+use hauchiwa::error::{HauchiwaError, RuntimeError};
+use hauchiwa::loader::{Document, Stylesheet, Svelte, TemplateEnv};
+use hauchiwa::prelude::*;
+use minijinja::Value;
+use serde::Serialize;
+
+use crate::datalog::Solution;
+use crate::model::Wiki;
+use crate::plugin::links::WikiLinks;
+use crate::props::PropsBare;
+use crate::{Context, Global};
+
+use super::{Garden, RenderedItem, TypstPage};
+
+const ENTRY: &str = "src/plugin/wiki/graph/App.svelte";
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub(super) struct Props {
+    url: String,
+}
+
+#[derive(Serialize)]
+struct Graph<'a> {
+    nodes: Vec<GraphNode<'a>>,
+    edges: Vec<GraphEdge<'a>>,
+}
+
+#[derive(Serialize)]
+struct GraphNode<'a> {
+    href: &'a str,
+    title: &'a str,
+    /// Top-level wiki directory the page belongs to, e.g. `math`.
+    section: &'a str,
+}
+
+#[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+struct GraphEdge<'a> {
+    source: &'a str,
+    target: &'a str,
+    kind: GraphEdgeKind,
+}
+
+#[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+enum GraphEdgeKind {
+    Link,
+    Parent,
+}
+
+pub(super) fn load(config: &mut Blueprint<Global>) -> Result<Many<Svelte<Props>>, HauchiwaError> {
+    let svelte = config
+        .load_svelte::<Props>()
+        .entry(ENTRY)?
+        .watch("src/plugin/wiki/graph/")?
+        .register();
+
+    Ok(svelte)
+}
+
+/// Serializes the garden graph, kept apart from the pages so the payload
+/// doesn't end up in the sitemap.
+pub(super) fn build(
+    config: &mut Blueprint<Global>,
+    documents: Many<Document<Wiki>>,
+    typst: Many<TypstPage>,
+    links: One<WikiLinks>,
+    garden: One<Garden>,
+) -> One<Output> {
+    config
+        .task()
+        .name("teien-graph")
+        .using((documents, typst, links, garden))
+        .merge(|_, (documents, typst, links, garden)| {
+            let doc_map = super::make_doc_map(&documents, &typst, links);
+            let data = serde_json::to_string(&build_graph(&doc_map, &garden.solution))?;
+
+            // only glob tasks get a `Store`, so the payload is named the way
+            // the store names hashed assets, by its digest under `/hash/`
+            let path = {
+                let digest = Blake2b::<U32>::digest(data.as_bytes());
+                format!("hash/{}.json", HEXLOWER.encode(&digest))
+            };
+
+            Ok(Output::binary(path, data))
+        })
+}
+
+/// Renders the `/wiki/graph/` page, which fetches the serialized graph.
+pub(super) fn render(
+    ctx: &Context,
+    templates: &TemplateEnv,
+    svelte: &Tracker<Svelte<Props>>,
+    styles: &[&Stylesheet],
+    graph: &Output,
+) -> Result<Output, RuntimeError> {
+    let Svelte {
+        prerender,
+        hydration,
+        ..
+    } = svelte.get(ENTRY)?;
+
+    let props = Props {
+        url: format!("/{}", graph.path),
+    };
+
+    let scripts = &[hydration];
+
+    let prerendered = prerender(&props)?;
+
+    let page_props = PropsBare {
+        head: crate::plugin::make_props_head(ctx, "Graph".to_string(), styles, scripts)?,
+        content: Value::from_safe_string(format!("<main>{prerendered}</main>")),
+    };
+
+    let tmpl = templates.get_template("layouts/bare.jinja")?;
+    let html = tmpl.render(&page_props)?;
+
+    Ok(Output::html("wiki/graph", html))
+}
+
+fn build_graph<'a>(
+    doc_map: &'a HashMap<String, RenderedItem>,
+    solution: &'a Solution,
+) -> Graph<'a> {
+    let mut nodes = doc_map
+        .iter()
+        .filter(|(_, item)| !matches!(item, RenderedItem::External { .. }))
+        .map(|(href, item)| GraphNode {
+            href,
+            title: item.title(),
+            section: href.split('/').nth(2).unwrap_or(""),
+        })
+        .collect::<Vec<_>>();
+
+    nodes.sort_unstable_by_key(|node| node.href);
+
+    let is_node = |href: &str| nodes.binary_search_by_key(&href, |node| node.href).is_ok();
+
+    let mut edges = Vec::new();
+
+    for node in &nodes {
+        for source in solution.get_backlinks(node.href).unwrap_or_default() {
+            if is_node(source) {
+                edges.push(GraphEdge {
+                    source,
+                    target: node.href,
+                    kind: GraphEdgeKind::Link,
+                });
+            }
+        }
+
+        // directories without an index page are skipped over
+        let mut parent = solution.get_parent(node.href);
+        while let Some(href) = parent {
+            if is_node(href) {
+                edges.push(GraphEdge {
+                    source: href,
+                    target: node.href,
+                    kind: GraphEdgeKind::Parent,
+                });
+                break;
+            }
+            parent = solution.get_parent(href);
+        }
+    }
+
+    edges.sort_unstable();
+    edges.dedup();
+
+    Graph { nodes, edges }
+}
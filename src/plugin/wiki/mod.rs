@@ -0,0 +1,689 @@
+mod graph;
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::ValueEnum;
+use hauchiwa::Tracker;
+use hauchiwa::error::{HauchiwaError, RuntimeError};
+use hauchiwa::loader::{Document, Image, Stylesheet, TemplateEnv};
+use hauchiwa::output;
+use hauchiwa::prelude::*;
+use minijinja::Value;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::datalog::Solution;
+use crate::model::{TypstRender, Wiki};
+use crate::plugin::links::WikiLinks;
+use crate::props::{
+    PropsHead, PropsWiki, PropsWikiBacklink, PropsWikiLink, PropsWikiMaintenance, PropsWikiPdf,
+    PropsWikiTreeNode,
+};
+use crate::{Bibtex, Csl, Global};
+
+enum RenderedItem<'a> {
+    Markdown(&'a Document<Wiki>),
+    Typst {
+        title: String,
+    },
+    /// Page from another collection, such as a post, linking into the garden.
+    External {
+        title: String,
+    },
+}
+
+impl RenderedItem<'_> {
+    fn title(&self) -> &str {
+        match self {
+            RenderedItem::Markdown(doc) => &doc.matter.title,
+            RenderedItem::Typst { title } | RenderedItem::External { title } => title,
+        }
+    }
+}
+
+/// The garden's index page, from which every other page should be reachable.
+const WIKI_ROOT: &str = "/wiki/";
+
+/// How many pages are listed in the "Related" section.
+const RELATED_LIMIT: usize = 5;
+
+/// A wiki page written in Typst and compiled to PDF.
+pub struct TypstPage {
+    /// Path to the `.typ` source file.
+    pub path: Utf8PathBuf,
+    /// Path to the compiled PDF.
+    pub pdf: Utf8PathBuf,
+    /// Page rendered for the web, unless it's published as a PDF only.
+    pub html: Option<String>,
+    /// Site hrefs linked from the source with `#link("/...")`.
+    pub links: Vec<String>,
+    /// Title from the document metadata, or the file stem.
+    pub title: String,
+    pub desc: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// Metadata a Typst page can declare about itself, either as
+/// `#metadata((title: "...")) <metadata>` or as a leading comment block:
+///
+/// ```typst
+/// // title: Linear algebra
+/// // description: Vector spaces and linear maps
+/// // tags: math, algebra
+/// // render: html
+/// ```
+#[derive(Deserialize, Default)]
+struct TypstMeta {
+    title: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    render: Option<TypstRender>,
+}
+
+type TeienSources = (Many<Document<Wiki>>, Many<TypstPage>);
+
+// Matches #link("/wiki/some/page/") in Typst sources
+static RE_TYPST_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"#link\(\s*"(/[^"]*)""#).expect("Invalid Typst link Regex"));
+
+/// Collects the site-local links in a Typst source, normalised to page hrefs.
+fn typst_links(source: &str) -> Vec<String> {
+    RE_TYPST_LINK
+        .captures_iter(source)
+        .map(|caps| {
+            let href = caps[1].split(['#', '?']).next().unwrap_or_default();
+            if href.ends_with('/') || Utf8Path::new(href).extension().is_some() {
+                href.to_string()
+            } else {
+                format!("{href}/")
+            }
+        })
+        .collect()
+}
+
+/// Reads the page metadata, querying the document only when it has a
+/// `<metadata>` label.
+fn typst_meta(path: &Utf8Path, source: &str) -> Result<TypstMeta, RuntimeError> {
+    if source.contains("<metadata>") {
+        let args = ["query", "-", "<metadata>", "--field=value", "--one"];
+        let output = run_typst(path, &args, source.as_bytes())?;
+        let meta = serde_json::from_slice(&output).map_err(|err| {
+            RuntimeError::msg(format!("Invalid Typst <metadata> in '{path}': {err}"))
+        })?;
+
+        return Ok(meta);
+    }
+
+    let mut meta = TypstMeta::default();
+
+    let comments = source
+        .lines()
+        .map_while(|line| line.trim().strip_prefix("//"))
+        .filter_map(|line| line.split_once(':'));
+
+    for (key, value) in comments {
+        let value = value.trim();
+        match key.trim() {
+            "title" => meta.title = Some(value.to_string()),
+            "description" => meta.description = Some(value.to_string()),
+            "render" => {
+                let render = TypstRender::from_str(value, true).map_err(|err| {
+                    RuntimeError::msg(format!("Invalid Typst render mode in '{path}': {err}"))
+                })?;
+                meta.render = Some(render);
+            }
+            "tags" => {
+                meta.tags = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(String::from)
+                    .collect()
+            }
+            _ => {}
+        }
+    }
+
+    Ok(meta)
+}
+
+/// Runs the Typst CLI, attributing failures to the page's source file.
+fn run_typst(path: &Utf8Path, args: &[&str], input: &[u8]) -> Result<Vec<u8>, RuntimeError> {
+    crate::typst::run_typst(args, input)
+        .map_err(|err| RuntimeError::msg(format!("Typst SSR failed for '{path}':\n{err}")))
+}
+
+pub fn load_teien(config: &mut Blueprint<Global>) -> Result<TeienSources, HauchiwaError> {
+    let documents = config
+        .load_documents::<Wiki>()
+        .glob("content/wiki/**/*.md")?
+        .base("content")
+        .register();
+
+    let typst = config
+        .task()
+        .name("wiki:typst")
+        .glob("content/wiki/**/*.typ")?
+        .map(|ctx, store, input| {
+            let data = input.read()?;
+
+            let output = run_typst(&input.path, &["c", "--format=pdf", "-", "-"], &data)?;
+            let pdf = store.save(&output, "pdf")?;
+
+            let source = String::from_utf8_lossy(&data);
+            let links = typst_links(&source);
+            let meta = typst_meta(&input.path, &source)?;
+
+            let html = match meta.render.unwrap_or(ctx.env.data.typst) {
+                TypstRender::Pdf => None,
+                TypstRender::Svg => Some(crate::typst::render_svg_pages(&data)),
+                TypstRender::Html => Some(crate::typst::render_html_body(&data)),
+            };
+
+            let html = html.transpose().map_err(|err| {
+                RuntimeError::msg(format!("Typst SSR failed for '{}':\n{err}", input.path))
+            })?;
+
+            let title = match meta.title {
+                Some(title) => title,
+                None => input.path.file_stem().unwrap_or("Untitled").to_string(),
+            };
+
+            Ok(TypstPage {
+                path: input.path,
+                pdf,
+                html,
+                links,
+                title,
+                desc: meta.description,
+                tags: meta.tags,
+            })
+        });
+
+    Ok((documents, typst))
+}
+
+/// Parsed Markdown pages of the garden along with the solved relationships
+/// between all of its pages.
+struct Garden {
+    /// Parsed Markdown documents by href.
+    parsed: HashMap<String, crate::md::Parsed>,
+    solution: Solution,
+}
+
+/// Maps each href in the garden, and each page linking into it, to its item.
+fn make_doc_map<'a>(
+    documents: &'a Tracker<Document<Wiki>>,
+    typst: &Tracker<TypstPage>,
+    links: &WikiLinks,
+) -> HashMap<String, RenderedItem<'a>> {
+    let mut doc_map = HashMap::new();
+
+    for (_, document) in documents {
+        doc_map.insert(
+            document.meta.href.to_string(),
+            RenderedItem::Markdown(document),
+        );
+    }
+
+    for (_, typst) in typst {
+        let href = output::source_to_href(&typst.path, Some("content"));
+        let title = typst.title.clone();
+        doc_map.insert(href.to_string(), RenderedItem::Typst { title });
+    }
+
+    for external in &links.external {
+        let title = external.title.clone();
+        doc_map.insert(external.href.clone(), RenderedItem::External { title });
+    }
+
+    doc_map
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn add_teien(
+    config: &mut Blueprint<Global>,
+    templates: One<TemplateEnv>,
+    documents: Many<Document<Wiki>>,
+    typst: Many<TypstPage>,
+    images: Many<Image>,
+    styles: Many<Stylesheet>,
+    bibtex: Many<Bibtex>,
+    csl: Many<Csl>,
+    links: One<WikiLinks>,
+) -> Result<One<Vec<Output>>, HauchiwaError> {
+    let svelte = graph::load(config)?;
+
+    let garden = config
+        .task()
+        .name("teien-garden")
+        .using((documents, images, bibtex, typst, csl, links))
+        .merge(|_, deps| {
+            let (documents, images, bibtex, typst, csl, links) = deps;
+
+            // href -> document
+            let doc_map = make_doc_map(&documents, &typst, links);
+
+            // this can track complex relationships between documents
+            let mut datalog = crate::datalog::Datalog::new();
+
+            // Datalog: add links pointing into the garden from other collections
+            for external in &links.external {
+                for target_href in &external.targets {
+                    if doc_map.contains_key(target_href.as_str()) {
+                        datalog.add_link(&external.href, target_href);
+                    }
+                }
+            }
+
+            for (_, typst) in &typst {
+                let href = output::source_to_href(&typst.path, Some("content"));
+
+                // Datalog: add links from the Typst source
+                for target_href in &typst.links {
+                    if doc_map.contains_key(target_href.as_str()) {
+                        datalog.add_link(&href, target_href);
+                    }
+                }
+
+                hauchiwa::tracing::info!("{}", &href);
+
+                datalog.add_page(&href);
+                add_parent_hierarchy(&mut datalog, &href);
+            }
+
+            // pass 1: parse markdown
+            let mut parsed = HashMap::new();
+
+            for (_, document) in &documents {
+                let library = bibtex.glob(&document.meta.assets("*.bib"))?.next();
+
+                let style = super::get_citation_style(
+                    document.matter.csl.as_deref(),
+                    &document.meta,
+                    &csl,
+                )?;
+
+                let markdown = crate::md::parse(
+                    &document.text,
+                    &document.meta,
+                    Some(&links.resolver),
+                    Some(&images),
+                    library.map(|library| crate::md::Library {
+                        data: &library.1.data,
+                        style: &style,
+                        mode: document.matter.bibliography,
+                        locale: document.matter.locale.as_deref(),
+                    }),
+                )?;
+
+                let href = document.meta.href.clone();
+
+                // Datalog: add wiki links
+                for target_href in &markdown.refs {
+                    if doc_map.contains_key(target_href.as_str()) {
+                        datalog.add_link(&href, target_href);
+                    }
+                }
+
+                hauchiwa::tracing::info!("{}", &href);
+                datalog.add_page(&href);
+                add_parent_hierarchy(&mut datalog, &href);
+
+                parsed.insert(href, markdown);
+            }
+
+            datalog.add_root(WIKI_ROOT);
+
+            // here we can solve the datalog rules
+            let solution = datalog.solve();
+
+            report_maintenance(&solution);
+
+            Ok(Garden { parsed, solution })
+        });
+
+    let graph = graph::build(config, documents, typst, links, garden);
+
+    let task = config
+        .task()
+        .using((
+            templates, documents, styles, typst, links, svelte, garden, graph,
+        ))
+        .merge(|ctx, deps| {
+            let (templates, documents, styles, typst, links, svelte, garden, graph) = deps;
+
+            let styles_graph = &[
+                styles.get("styles/styles.scss")?,
+                styles.get("styles/graph.scss")?,
+            ];
+
+            let styles = &[
+                styles.get("styles/styles.scss")?,
+                styles.get("styles/layouts/page.scss")?,
+            ];
+
+            let doc_map = make_doc_map(&documents, &typst, links);
+            let solution = &garden.solution;
+
+            // pass 2: render html
+            let mut pages = vec![];
+
+            for (_, document) in &documents {
+                let href = &document.meta.href;
+                let markdown = garden
+                    .parsed
+                    .get(href)
+                    .ok_or_else(|| RuntimeError::msg(format!("wiki: {href} wasn't parsed")))?;
+
+                let backlinks = get_backlinks(href, &doc_map, solution);
+
+                let bibliography = markdown.bibliography.as_ref().map(|bib| {
+                    bib.iter()
+                        .map(|item| Value::from_safe_string(item.clone()))
+                        .collect()
+                });
+
+                let props = PropsWiki {
+                    head: super::make_props_head(ctx, document.matter.title.clone(), styles, &[])?,
+                    navbar: super::make_props_navbar(),
+                    footer: super::make_props_footer(ctx),
+                    title: document.matter.title.clone(),
+                    tree: build_tree_nodes(href, "/", &doc_map, solution),
+                    content: Value::from_safe_string(markdown.html.clone()),
+                    bibliography,
+                    backlinks,
+                    related: get_related(href, &doc_map, solution),
+                    pdf_path: None,
+                };
+
+                let tmpl = templates.get_template("wiki.jinja")?;
+                let page = tmpl.render(&props)?;
+
+                pages.push(Output::to(document).html(page)?);
+            }
+
+            for (_, typst) in &typst {
+                let href = &*output::source_to_href(&typst.path, Some("content"));
+
+                if let Some(html) = &typst.html {
+                    let props = PropsWiki {
+                        head: PropsHead {
+                            description: typst.desc.clone(),
+                            keywords: typst.tags.clone(),
+                            ..super::make_props_head(ctx, typst.title.clone(), styles, &[])?
+                        },
+                        navbar: super::make_props_navbar(),
+                        footer: super::make_props_footer(ctx),
+                        title: typst.title.clone(),
+                        tree: build_tree_nodes(href, "/", &doc_map, solution),
+                        content: Value::from_safe_string(html.clone()),
+                        bibliography: None,
+                        backlinks: get_backlinks(href, &doc_map, solution),
+                        related: get_related(href, &doc_map, solution),
+                        pdf_path: Some(typst.pdf.to_string()),
+                    };
+
+                    let tmpl = templates.get_template("wiki.jinja")?;
+                    let page = tmpl.render(&props)?;
+
+                    pages.push(Output::html(href.strip_prefix('/').unwrap_or(href), page));
+                    continue;
+                }
+
+                let props = PropsWikiPdf {
+                    head: PropsHead {
+                        description: typst.desc.clone(),
+                        keywords: typst.tags.clone(),
+                        ..super::make_props_head(ctx, typst.title.clone(), styles, &[])?
+                    },
+                    navbar: super::make_props_navbar(),
+                    footer: super::make_props_footer(ctx),
+                    title: typst.title.clone(),
+                    desc: typst.desc.clone(),
+                    tags: typst.tags.clone(),
+                    tree: build_tree_nodes(href, "/", &doc_map, solution),
+                    pdf_path: typst.pdf.to_string(),
+                    backlinks: get_backlinks(href, &doc_map, solution),
+                };
+
+                let tmpl = templates.get_template("wiki_pdf.jinja")?;
+                let html = tmpl.render(&props)?;
+
+                pages.push(Output::html(href.strip_prefix('/').unwrap_or(href), html));
+            }
+
+            {
+                let href = "/wiki/_maintenance/";
+
+                let to_links = |hrefs: Vec<&str>| {
+                    hrefs
+                        .into_iter()
+                        .filter_map(|h| {
+                            Some(PropsWikiLink {
+                                href: h.to_string(),
+                                title: doc_map.get(h)?.title().to_string(),
+                            })
+                        })
+                        .collect()
+                };
+
+                let props = PropsWikiMaintenance {
+                    head: super::make_props_head(ctx, "Maintenance".to_string(), styles, &[])?,
+                    navbar: super::make_props_navbar(),
+                    footer: super::make_props_footer(ctx),
+                    tree: build_tree_nodes(href, "/", &doc_map, solution),
+                    orphans: to_links(solution.get_orphans()),
+                    dead_ends: to_links(solution.get_dead_ends()),
+                    unreachable: to_links(solution.get_unreachable()),
+                };
+
+                let tmpl = templates.get_template("wiki_maintenance.jinja")?;
+                let html = tmpl.render(&props)?;
+
+                pages.push(Output::html(href.trim_matches('/'), html));
+            }
+
+            pages.push(graph::render(ctx, templates, &svelte, styles_graph, graph)?);
+
+            Ok(pages)
+        });
+
+    Ok(task)
+}
+
+fn get_backlinks(
+    href: &str,
+    doc_map: &HashMap<String, RenderedItem>,
+    solution: &crate::datalog::Solution,
+) -> Option<Vec<PropsWikiBacklink>> {
+    solution.get_backlinks(href).map(|hrefs| {
+        hrefs
+            .into_iter()
+            .filter_map(|h| {
+                Some(PropsWikiBacklink {
+                    href: h.to_string(),
+                    title: doc_map.get(h)?.title().to_string(),
+                })
+            })
+            .collect()
+    })
+}
+
+fn get_related(
+    href: &str,
+    doc_map: &HashMap<String, RenderedItem>,
+    solution: &crate::datalog::Solution,
+) -> Option<Vec<PropsWikiLink>> {
+    let related = solution
+        .get_related(href)
+        .into_iter()
+        .filter_map(|h| {
+            Some(PropsWikiLink {
+                href: h.to_string(),
+                title: doc_map.get(h)?.title().to_string(),
+            })
+        })
+        .take(RELATED_LIMIT)
+        .collect::<Vec<_>>();
+
+    (!related.is_empty()).then_some(related)
+}
+
+/// Logs the pages which are hard to find or lead nowhere.
+fn report_maintenance(solution: &crate::datalog::Solution) {
+    let report = [
+        ("orphaned", solution.get_orphans()),
+        ("dead-end", solution.get_dead_ends()),
+        ("unreachable", solution.get_unreachable()),
+    ];
+
+    for (kind, hrefs) in report {
+        if !hrefs.is_empty() {
+            hauchiwa::tracing::warn!("wiki: {} {kind} pages: {}", hrefs.len(), hrefs.join(", "));
+        }
+    }
+}
+
+fn build_tree_nodes(
+    active_href: &str,
+    parent_href: &str,
+    resolved: &HashMap<String, RenderedItem>,
+    solution: &crate::datalog::Solution,
+) -> Vec<PropsWikiTreeNode> {
+    let children = match solution.get_children(parent_href) {
+        Some(mut kids) => {
+            kids.sort();
+            kids
+        }
+        None => return vec![],
+    };
+
+    children
+        .into_iter()
+        .map(|child_href| {
+            let (name, is_link) = if let Some(doc) = resolved.get(child_href) {
+                (doc.title().to_string(), true)
+            } else {
+                let name = child_href
+                    .trim_end_matches('/')
+                    .split('/')
+                    .next_back()
+                    .unwrap_or(child_href)
+                    .to_string();
+                (name, false)
+            };
+
+            let is_active_path = is_same_or_descendant(active_href, child_href);
+            let is_current = active_href == child_href;
+
+            let children = if is_active_path || !is_link {
+                build_tree_nodes(active_href, child_href, resolved, solution)
+            } else {
+                vec![]
+            };
+
+            PropsWikiTreeNode {
+                href: child_href.to_string(),
+                name,
+                is_link,
+                is_current,
+                children,
+            }
+        })
+        .collect()
+}
+
+fn add_parent_hierarchy(datalog: &mut crate::datalog::Datalog, href: &str) {
+    let mut child = href.to_string();
+
+    while let Some(parent) = parent_href(&child) {
+        datalog.add_parent(&parent, &child);
+
+        if parent == "/" {
+            break;
+        }
+
+        child = parent;
+    }
+}
+
+fn is_same_or_descendant(active_href: &str, child_href: &str) -> bool {
+    if active_href == child_href {
+        return true;
+    }
+
+    let child_prefix = if child_href.ends_with('/') {
+        child_href.to_string()
+    } else {
+        format!("{child_href}/")
+    };
+
+    active_href.starts_with(&child_prefix)
+}
+
+fn parent_href(href: &str) -> Option<String> {
+    let href = href.trim_end_matches('/');
+    if href.is_empty() {
+        return None;
+    }
+
+    let parent = Utf8Path::new(href).parent()?;
+    let parent = parent.as_str();
+
+    match parent {
+        "" => None,
+        "/" => Some("/".to_string()),
+        _ => Some(format!("{parent}/")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_same_or_descendant;
+
+    #[test]
+    fn typst_links_are_normalised_to_hrefs() {
+        let source = r#"See #link("/wiki/math/algebra")[algebra], #link("/wiki/cs/#top")[cs],
+#link("https://example.com")[elsewhere] and #link("/static/paper.pdf")[the paper]."#;
+
+        assert_eq!(
+            super::typst_links(source),
+            ["/wiki/math/algebra/", "/wiki/cs/", "/static/paper.pdf"]
+        );
+    }
+
+    #[test]
+    fn typst_meta_is_read_from_a_leading_comment() {
+        let source =
+            "// title: Linear algebra\n// tags: math, algebra\n\n= Vectors\n// title: ignored\n";
+        let meta = super::typst_meta("content/wiki/math/la.typ".into(), source).unwrap();
+
+        assert_eq!(meta.title.as_deref(), Some("Linear algebra"));
+        assert_eq!(meta.description, None);
+        assert_eq!(meta.tags, ["math", "algebra"]);
+    }
+
+    #[test]
+    fn same_or_descendant_matches_path_segments() {
+        assert!(is_same_or_descendant("/wiki/cs/", "/wiki/cs/"));
+        assert!(is_same_or_descendant("/wiki/cs/firefox/", "/wiki/cs/"));
+        assert!(is_same_or_descendant(
+            "/wiki/cs/firefox/",
+            "/wiki/cs/firefox/"
+        ));
+
+        assert!(!is_same_or_descendant(
+            "/wiki/cs/firefox/",
+            "/wiki/cs/fire/"
+        ));
+        assert!(!is_same_or_descendant(
+            "/wiki/cs/firefox/",
+            "/wiki/cs/firefoxes/"
+        ));
+        assert!(!is_same_or_descendant("/wiki/cs/", "/wiki/cs/firefox/"));
+    }
+}
@@ -9,6 +9,19 @@ pub struct PropsHead {
     pub styles: Vec<String>,
     pub scripts: Vec<String>,
     pub refresh_script: Option<Value>,
+    pub feeds: Vec<PropsFeedLink>,
+    /// Page summary for `<meta name="description">`.
+    pub description: Option<String>,
+    /// Page tags for `<meta name="keywords">`.
+    pub keywords: Vec<String>,
+}
+
+/// A feed advertised with `<link rel="alternate">`.
+#[derive(Serialize)]
+pub struct PropsFeedLink {
+    pub title: String,
+    pub href: String,
+    pub mime: &'static str,
 }
 
 #[derive(Serialize)]
@@ -93,6 +106,13 @@ pub struct PropsMicroblogEntry {
     pub date_iso: String,
     pub date_display: String,
     pub timestamp: i64,
+    pub hash: String,
+}
+
+#[derive(Serialize)]
+pub struct PropsMicroblogAuthor {
+    pub nick: String,
+    pub avatar: String,
 }
 
 #[derive(Serialize)]
@@ -100,6 +120,7 @@ pub struct PropsThoughts {
     pub head: PropsHead,
     pub navbar: PropsNavbar,
     pub footer: PropsFooter,
+    pub author: PropsMicroblogAuthor,
     pub entries: Vec<PropsMicroblogEntry>,
 }
 
@@ -108,7 +129,10 @@ pub struct PropsThought {
     pub head: PropsHead,
     pub navbar: PropsNavbar,
     pub footer: PropsFooter,
+    pub author: PropsMicroblogAuthor,
     pub entry: PropsMicroblogEntry,
+    /// The whole conversation, including the entry itself.
+    pub thread: Vec<PropsMicroblogEntry>,
 }
 
 #[derive(Serialize)]
@@ -126,6 +150,12 @@ pub struct PropsWikiBacklink {
     pub title: String,
 }
 
+#[derive(Serialize)]
+pub struct PropsWikiLink {
+    pub href: String,
+    pub title: String,
+}
+
 #[derive(Serialize)]
 pub struct PropsWiki {
     pub head: PropsHead,
@@ -136,6 +166,20 @@ pub struct PropsWiki {
     pub content: Value,
     pub bibliography: Option<Vec<Value>>,
     pub backlinks: Option<Vec<PropsWikiBacklink>>,
+    pub related: Option<Vec<PropsWikiLink>>,
+    /// Download link for pages compiled from Typst.
+    pub pdf_path: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PropsWikiMaintenance {
+    pub head: PropsHead,
+    pub navbar: PropsNavbar,
+    pub footer: PropsFooter,
+    pub tree: Vec<PropsWikiTreeNode>,
+    pub orphans: Vec<PropsWikiLink>,
+    pub dead_ends: Vec<PropsWikiLink>,
+    pub unreachable: Vec<PropsWikiLink>,
 }
 
 #[derive(Serialize)]
@@ -143,8 +187,12 @@ pub struct PropsWikiPdf {
     pub head: PropsHead,
     pub navbar: PropsNavbar,
     pub footer: PropsFooter,
+    pub title: String,
+    pub desc: Option<String>,
+    pub tags: Vec<String>,
     pub tree: Vec<PropsWikiTreeNode>,
     pub pdf_path: String,
+    pub backlinks: Option<Vec<PropsWikiBacklink>>,
 }
 
 #[derive(Serialize)]
@@ -153,6 +201,8 @@ pub struct PropsTag {
     pub navbar: PropsNavbar,
     pub footer: PropsFooter,
     pub title: String,
+    pub rss: String,
+    pub icon_rss: Value,
     pub groups: Vec<PropsListGroup>,
 }
 
@@ -252,7 +302,7 @@ pub struct PropsList {
     pub navbar: PropsNavbar,
     pub footer: PropsFooter,
     pub title: String,
-    pub rss: &'static str,
+    pub rss: String,
     pub icon_rss: Value,
     pub groups: Vec<PropsListGroup>,
 }
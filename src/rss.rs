@@ -1,13 +1,24 @@
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+use atom_syndication::{Content, Link, Person, Text};
+use chrono::{DateTime, Utc};
 use hauchiwa::Output;
 use hauchiwa::camino::Utf8Path;
+use hauchiwa::error::RuntimeError;
 use hauchiwa::loader::Document;
-use rss::{ChannelBuilder, ItemBuilder};
+use regex::Regex;
+use rss::extension::atom::AtomExtension;
+use rss::{CategoryBuilder, ChannelBuilder, GuidBuilder, ItemBuilder};
+use serde::Serialize;
 
-use crate::BASE_URL;
-use crate::model::{Post, Project, Slideshow};
+use crate::model::{MicroblogEntry, Post, Project, Slideshow, Wiki};
+use crate::props::PropsFeedLink;
+use crate::{BASE_URL, Context};
 
-pub(crate) trait ToFeed: Sized {
-    fn to_feed(&self) -> rss::Item;
+pub(crate) trait ToFeed {
+    /// Builds the feed item, `html` being the rendered content of the page.
+    fn to_feed<'a>(&'a self, ctx: &Context, html: Option<&'a str>) -> Entry<'a>;
 }
 
 fn site_url(href: &str) -> String {
@@ -15,54 +26,563 @@ fn site_url(href: &str) -> String {
     Utf8Path::new(BASE_URL).join(link).to_string()
 }
 
-impl ToFeed for &Document<Post> {
-    fn to_feed(&self) -> rss::Item {
-        ItemBuilder::default()
-            .title(self.matter.title.clone())
-            .link(site_url(&self.meta.href))
-            .pub_date(self.matter.date.to_rfc2822())
-            .build()
+static RE_RELATIVE_URL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\b(href|src)="(/[^/"][^"]*|/|#[^"]*)""#).expect("Invalid regex")
+});
+
+static RE_SRCSET: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\bsrcset="([^"]*)""#).expect("Invalid regex"));
+
+/// Feed readers show content out of the site's context, so root-relative and
+/// fragment-only URLs have to be made absolute.
+fn absolute_links(html: &str, page: &str) -> String {
+    let html = RE_RELATIVE_URL.replace_all(html, |caps: &regex::Captures| {
+        let url = &caps[2];
+        let url = if url.starts_with('#') {
+            format!("{page}{url}")
+        } else {
+            site_url(url)
+        };
+        format!("{}=\"{url}\"", &caps[1])
+    });
+
+    // every candidate of a srcset has its own URL, e.g. `/a.webp 1x, /b.webp 2x`
+    RE_SRCSET
+        .replace_all(&html, |caps: &regex::Captures| {
+            let srcset = caps[1]
+                .split(',')
+                .map(|candidate| {
+                    let candidate = candidate.trim();
+                    match candidate.split_once(char::is_whitespace) {
+                        Some((url, desc)) => format!("{} {desc}", absolute_url(url)),
+                        None => absolute_url(candidate),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("srcset=\"{srcset}\"")
+        })
+        .into_owned()
+}
+
+fn absolute_url(url: &str) -> String {
+    if url.starts_with('/') && !url.starts_with("//") {
+        site_url(url)
+    } else {
+        url.to_string()
     }
 }
 
-impl ToFeed for &Document<Slideshow> {
-    fn to_feed(&self) -> rss::Item {
-        ItemBuilder::default()
-            .title(self.matter.title.clone())
-            .link(site_url(&self.meta.href))
-            .pub_date(self.matter.date.to_rfc2822())
-            .build()
+/// Name of the author for formats which require one.
+const AUTHOR: &str = "Maciej Jur";
+
+/// Feeds of the collections, as `(slug, title)`.
+pub const FEED_POSTS: (&str, &str) = ("posts", "Kamoshi.org Posts");
+pub const FEED_SLIDES: (&str, &str) = ("slides", "Kamoshi.org Slides");
+pub const FEED_PROJECTS: (&str, &str) = ("projects", "Kamoshi.org Projects");
+pub const FEED_THOUGHTS: (&str, &str) = ("thoughts", "Kamoshi.org Thoughts");
+
+/// The formats each feed is published in.
+#[derive(Debug, Clone, Copy)]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+    Json,
+}
+
+impl FeedFormat {
+    pub const ALL: [FeedFormat; 3] = [FeedFormat::Rss, FeedFormat::Atom, FeedFormat::Json];
+
+    pub fn file(self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "rss.xml",
+            FeedFormat::Atom => "atom.xml",
+            FeedFormat::Json => "feed.json",
+        }
+    }
+
+    pub fn mime(self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "application/rss+xml",
+            FeedFormat::Atom => "application/atom+xml",
+            FeedFormat::Json => "application/feed+json",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "RSS",
+            FeedFormat::Atom => "Atom",
+            FeedFormat::Json => "JSON Feed",
+        }
     }
 }
 
-impl ToFeed for &Document<Project> {
-    fn to_feed(&self) -> rss::Item {
+/// Path of the feed merging every collection.
+const SITE_FEED: &str = "feed.xml";
+
+/// Titles of microblog entries are cut to this many characters.
+const TITLE_LENGTH: usize = 60;
+
+/// `<link rel="alternate">` entries for a page. Every page advertises the site
+/// feed, pages of a collection also advertise the feeds of that collection.
+pub fn alternates(feed: Option<(&str, &str)>) -> Vec<PropsFeedLink> {
+    let site = PropsFeedLink {
+        title: "Kamoshi.org".into(),
+        href: format!("/{SITE_FEED}"),
+        mime: FeedFormat::Rss.mime(),
+    };
+
+    std::iter::once(site)
+        .chain(feed.into_iter().flat_map(|(slug, title)| {
+            FeedFormat::ALL.map(|format| PropsFeedLink {
+                title: format!("{title} ({})", format.name()),
+                href: format!("/{slug}/{}", format.file()),
+                mime: format.mime(),
+            })
+        }))
+        .collect()
+}
+
+/// A feed item, independent of the format it ends up in.
+pub(crate) struct Entry<'a> {
+    title: Cow<'a, str>,
+    link: String,
+    /// Unique ID of the entry, the link unless the page is republished with
+    /// every update.
+    guid: String,
+    desc: Option<Cow<'a, str>>,
+    html: Option<Cow<'a, str>>,
+    tags: &'a [String],
+    date: Option<DateTime<Utc>>,
+}
+
+impl Entry<'_> {
+    /// Rendered content, with links that work outside of the site.
+    fn content(&self) -> Option<String> {
+        self.html
+            .as_deref()
+            .map(|html| absolute_links(html, &self.link))
+    }
+
+    fn to_rss(&self) -> rss::Item {
+        let guid = GuidBuilder::default()
+            .value(self.guid.clone())
+            .permalink(self.guid == self.link)
+            .build();
+
+        let categories = self
+            .tags
+            .iter()
+            .map(|tag| CategoryBuilder::default().name(tag.clone()).build())
+            .collect::<Vec<_>>();
+
         ItemBuilder::default()
-            .title(self.matter.title.clone())
-            .link(
-                self.matter
-                    .link
-                    .clone()
-                    .unwrap_or_else(|| site_url(&self.meta.href)),
-            )
+            .title(self.title.to_string())
+            .link(self.link.clone())
+            .description(self.desc.as_deref().map(str::to_string))
+            .content(self.content())
+            .categories(categories)
+            .pub_date(self.date.map(|date| date.to_rfc2822()))
+            .guid(guid)
             .build()
     }
+
+    fn to_atom(&self, updated: DateTime<Utc>) -> atom_syndication::Entry {
+        let date = self.date.unwrap_or(updated).fixed_offset();
+
+        atom_syndication::Entry {
+            title: Text::plain(self.title.clone()),
+            id: self.guid.clone(),
+            updated: date,
+            published: self.date.map(|date| date.fixed_offset()),
+            links: vec![Link {
+                href: self.link.clone(),
+                rel: "alternate".into(),
+                ..Default::default()
+            }],
+            summary: self.desc.as_deref().map(Text::plain),
+            content: self.content().map(|html| Content {
+                value: Some(html),
+                content_type: Some("html".into()),
+                ..Default::default()
+            }),
+            categories: self
+                .tags
+                .iter()
+                .map(|tag| atom_syndication::Category {
+                    term: tag.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn to_json(&self) -> JsonFeedItem<'_> {
+        JsonFeedItem {
+            content_html: self.content(),
+            id: &self.guid,
+            url: &self.link,
+            title: &self.title,
+            summary: self.desc.as_deref(),
+            date_published: self.date.map(|date| date.to_rfc3339()),
+            tags: self.tags,
+        }
+    }
+}
+
+/// JSON Feed 1.1, see <https://www.jsonfeed.org/version/1.1/>.
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    home_page_url: String,
+    feed_url: String,
+    authors: [JsonFeedAuthor; 1],
+    items: Vec<JsonFeedItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAuthor {
+    name: &'static str,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem<'a> {
+    id: &'a str,
+    url: &'a str,
+    title: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_published: Option<String>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    tags: &'a [String],
+}
+
+impl ToFeed for Document<Post> {
+    fn to_feed<'a>(&'a self, _: &Context, html: Option<&'a str>) -> Entry<'a> {
+        let link = site_url(&self.meta.href);
+
+        Entry {
+            title: Cow::Borrowed(&self.matter.title),
+            guid: link.clone(),
+            link,
+            desc: self.matter.desc.as_deref().map(Cow::Borrowed),
+            html: html.map(Cow::Borrowed),
+            tags: &self.matter.tags,
+            date: Some(self.matter.date),
+        }
+    }
+}
+
+impl ToFeed for Document<Slideshow> {
+    fn to_feed<'a>(&'a self, _: &Context, html: Option<&'a str>) -> Entry<'a> {
+        let link = site_url(&self.meta.href);
+
+        Entry {
+            title: Cow::Borrowed(&self.matter.title),
+            guid: link.clone(),
+            link,
+            desc: self.matter.desc.as_deref().map(Cow::Borrowed),
+            html: html.map(Cow::Borrowed),
+            tags: &[],
+            date: Some(self.matter.date.to_utc()),
+        }
+    }
+}
+
+impl ToFeed for Document<Project> {
+    fn to_feed<'a>(&'a self, ctx: &Context, html: Option<&'a str>) -> Entry<'a> {
+        // projects have no date of their own, so the first commit stands in
+        let date = ctx
+            .env
+            .data
+            .repo
+            .as_ref()
+            .and_then(|repo| repo.files.get(self.meta.path.as_str()))
+            .and_then(|history| history.last())
+            .map(|info| info.commit_date.to_utc());
+
+        let link = self
+            .matter
+            .link
+            .clone()
+            .unwrap_or_else(|| site_url(&self.meta.href));
+
+        Entry {
+            title: Cow::Borrowed(&self.matter.title),
+            guid: link.clone(),
+            link,
+            desc: self.matter.desc.as_deref().map(Cow::Borrowed),
+            html: html.map(Cow::Borrowed),
+            tags: &self.matter.tech,
+            date,
+        }
+    }
+}
+
+impl ToFeed for Document<Wiki> {
+    /// Wiki pages are living documents, so each commit touching the page
+    /// shows up as a new entry.
+    fn to_feed<'a>(&'a self, ctx: &Context, html: Option<&'a str>) -> Entry<'a> {
+        let commit = ctx
+            .env
+            .data
+            .repo
+            .as_ref()
+            .and_then(|repo| repo.files.get(self.meta.path.as_str()))
+            .and_then(|history| history.first());
+
+        let link = site_url(&self.meta.href);
+        let guid = match commit {
+            Some(commit) => format!("{link}#{}", commit.abbreviated_hash),
+            None => link.clone(),
+        };
+
+        Entry {
+            title: Cow::Borrowed(&self.matter.title),
+            guid,
+            link,
+            desc: commit.map(|commit| Cow::Owned(commit.subject.clone())),
+            html: html.map(Cow::Borrowed),
+            tags: &[],
+            date: commit.map(|commit| commit.commit_date.to_utc()),
+        }
+    }
+}
+
+impl ToFeed for MicroblogEntry {
+    fn to_feed<'a>(&'a self, _: &Context, html: Option<&'a str>) -> Entry<'a> {
+        let link = site_url(&format!("/thoughts/{}", self.date.timestamp()));
+
+        let html = html.map_or_else(
+            || Cow::Owned(crate::plugin::twtxt::render_body(self)),
+            Cow::Borrowed,
+        );
+
+        Entry {
+            title: Cow::Owned(first_sentence(&self.plain_text())),
+            guid: link.clone(),
+            link,
+            desc: None,
+            html: Some(html),
+            tags: &[],
+            date: Some(self.date),
+        }
+    }
+}
+
+/// Derives a title from the first sentence of a microblog entry, which is cut
+/// short when it's too long.
+fn first_sentence(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default().trim();
+
+    let sentence = line
+        .char_indices()
+        .find(|&(i, c)| match c {
+            '。' | '！' | '？' => true,
+            // dots inside words, e.g. in `v0.2`, don't end the sentence
+            '.' | '!' | '?' => line[i + 1..].chars().next().is_none_or(char::is_whitespace),
+            _ => false,
+        })
+        .map_or(line, |(i, c)| &line[..i + c.len_utf8()]);
+
+    match sentence.char_indices().nth(TITLE_LENGTH) {
+        Some((end, _)) => format!("{}…", sentence[..end].trim_end()),
+        None => sentence.to_string(),
+    }
 }
 
-pub fn generate_feed<'a, T>(data: &[&'a T], slug: &'static str, title: &'static str) -> Output
-where
-    &'a T: ToFeed + Clone,
-{
+/// Renders the feeds at `{slug}/rss.xml`, `{slug}/atom.xml` and
+/// `{slug}/feed.json`. Each document comes with its rendered HTML, if it
+/// should be included in full.
+pub fn generate_feed<T: ToFeed>(
+    ctx: &Context,
+    data: &[(&T, Option<String>)],
+    (slug, title): (&str, &str),
+) -> Result<Vec<Output>, RuntimeError> {
     let slug = Utf8Path::new(slug);
-    let data = data.iter().map(ToFeed::to_feed).collect::<Vec<_>>();
-
-    Output::binary(
-        slug.join("rss.xml"),
-        ChannelBuilder::default()
-            .title(title)
-            .link(Utf8Path::new(BASE_URL).join(slug).to_string())
-            .items(data)
-            .build()
-            .to_string(),
-    )
+    let home = site_url(slug.as_str());
+
+    let entries = data
+        .iter()
+        .map(|(doc, html)| doc.to_feed(ctx, html.as_deref()))
+        .collect::<Vec<_>>();
+
+    let updated = last_updated(&entries);
+    let feed_url = |format: FeedFormat| site_url(slug.join(format.file()).as_str());
+
+    let rss = to_channel(&entries, title, &home, &feed_url(FeedFormat::Rss), updated);
+
+    let atom = atom_syndication::Feed {
+        title: Text::plain(title),
+        id: feed_url(FeedFormat::Atom),
+        updated: updated.fixed_offset(),
+        authors: vec![Person {
+            name: AUTHOR.into(),
+            ..Default::default()
+        }],
+        links: vec![
+            Link {
+                href: feed_url(FeedFormat::Atom),
+                rel: "self".into(),
+                mime_type: Some(FeedFormat::Atom.mime().into()),
+                ..Default::default()
+            },
+            Link {
+                href: home.clone(),
+                rel: "alternate".into(),
+                ..Default::default()
+            },
+        ],
+        entries: entries.iter().map(|entry| entry.to_atom(updated)).collect(),
+        ..Default::default()
+    };
+
+    let json = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title,
+        home_page_url: home,
+        feed_url: feed_url(FeedFormat::Json),
+        authors: [JsonFeedAuthor { name: AUTHOR }],
+        items: entries.iter().map(Entry::to_json).collect(),
+    };
+
+    Ok(vec![
+        Output::binary(slug.join(FeedFormat::Rss.file()), rss.to_string()),
+        Output::binary(slug.join(FeedFormat::Atom.file()), atom.to_string()),
+        Output::binary(
+            slug.join(FeedFormat::Json.file()),
+            serde_json::to_string(&json)?,
+        ),
+    ])
+}
+
+/// Renders the RSS feed at `/feed.xml`, which merges every collection, newest
+/// first. Posts and slides come with their rendered HTML, like in the feeds of
+/// their own collections.
+pub fn generate_site_feed(
+    ctx: &Context,
+    posts: &[(&Document<Post>, Option<String>)],
+    slides: &[(&Document<Slideshow>, Option<String>)],
+    microblog: &[MicroblogEntry],
+    wiki: &[&Document<Wiki>],
+) -> Output {
+    let mut entries = posts
+        .iter()
+        .map(|(doc, html)| doc.to_feed(ctx, html.as_deref()))
+        .chain(
+            slides
+                .iter()
+                .map(|(doc, html)| doc.to_feed(ctx, html.as_deref())),
+        )
+        .chain(microblog.iter().map(|entry| entry.to_feed(ctx, None)))
+        .chain(wiki.iter().map(|doc| doc.to_feed(ctx, None)))
+        .filter(|entry| entry.date.is_some())
+        .collect::<Vec<_>>();
+
+    entries.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.guid.cmp(&b.guid)));
+
+    let channel = to_channel(
+        &entries,
+        "Kamoshi.org",
+        BASE_URL,
+        &site_url(SITE_FEED),
+        last_updated(&entries),
+    );
+
+    Output::binary(SITE_FEED, channel.to_string())
+}
+
+/// The date of the newest entry, so that feeds only change along with their
+/// content.
+fn last_updated(entries: &[Entry]) -> DateTime<Utc> {
+    entries
+        .iter()
+        .filter_map(|entry| entry.date)
+        .max()
+        .unwrap_or(DateTime::UNIX_EPOCH)
+}
+
+fn to_channel(
+    entries: &[Entry],
+    title: &str,
+    home: &str,
+    self_url: &str,
+    updated: DateTime<Utc>,
+) -> rss::Channel {
+    ChannelBuilder::default()
+        .title(title)
+        .link(home)
+        .last_build_date(updated.to_rfc2822())
+        .atom_ext(AtomExtension {
+            links: vec![Link {
+                href: self_url.into(),
+                rel: "self".into(),
+                mime_type: Some(FeedFormat::Rss.mime().into()),
+                ..Default::default()
+            }],
+        })
+        .items(entries.iter().map(Entry::to_rss).collect::<Vec<_>>())
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn titles_are_taken_from_the_first_sentence() {
+        assert_eq!(
+            super::first_sentence("Rewrote the parser. It is faster now."),
+            "Rewrote the parser."
+        );
+        assert_eq!(super::first_sentence("v0.2.1 is out!"), "v0.2.1 is out!");
+        assert_eq!(super::first_sentence("今日は雨。散歩は中止"), "今日は雨。");
+        assert_eq!(
+            super::first_sentence(&"a".repeat(80)),
+            format!("{}…", "a".repeat(60))
+        );
+    }
+
+    #[test]
+    fn pages_advertise_only_their_own_feeds() {
+        let hrefs = |feed| {
+            super::alternates(feed)
+                .into_iter()
+                .map(|link| link.href)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(hrefs(None), ["/feed.xml"]);
+        assert_eq!(
+            hrefs(Some(super::FEED_POSTS)),
+            [
+                "/feed.xml",
+                "/posts/rss.xml",
+                "/posts/atom.xml",
+                "/posts/feed.json"
+            ]
+        );
+    }
+
+    #[test]
+    fn relative_links_are_made_absolute() {
+        let html = r##"<a href="/posts/a/">a</a> <a href="#note">b</a> <img src="//cdn.example/x.png"> <a href="https://example.com/">c</a>"##;
+
+        assert_eq!(
+            super::absolute_links(html, "https://kamoshi.org/posts/b/"),
+            r##"<a href="https://kamoshi.org/posts/a/">a</a> <a href="https://kamoshi.org/posts/b/#note">b</a> <img src="//cdn.example/x.png"> <a href="https://example.com/">c</a>"##
+        );
+
+        let html =
+            r#"<source srcset="/hash/a.avif 1x, /hash/b.avif 2x"><img srcset="/hash/c.webp">"#;
+
+        assert_eq!(
+            super::absolute_links(html, "https://kamoshi.org/posts/b/"),
+            r#"<source srcset="https://kamoshi.org/hash/a.avif 1x, https://kamoshi.org/hash/b.avif 2x"><img srcset="https://kamoshi.org/hash/c.webp">"#
+        );
+    }
 }
@@ -71,6 +71,13 @@ static CONFIGS: LazyLock<HashMap<&'static str, HighlightConfiguration>> = LazyLo
             "",
             "",
         ),
+        language!(
+            "dockerfile",
+            tree_sitter_containerfile::LANGUAGE,
+            tree_sitter_containerfile::HIGHLIGHTS_QUERY,
+            tree_sitter_containerfile::INJECTIONS_QUERY,
+            "",
+        ),
         language!(
             "css",
             tree_sitter_css::LANGUAGE,
@@ -78,6 +85,13 @@ static CONFIGS: LazyLock<HashMap<&'static str, HighlightConfiguration>> = LazyLo
             "",
             "",
         ),
+        language!(
+            "go",
+            tree_sitter_go::LANGUAGE,
+            tree_sitter_go::HIGHLIGHTS_QUERY,
+            "",
+            "",
+        ),
         language!(
             "haskell",
             tree_sitter_haskell::LANGUAGE,
@@ -106,6 +120,13 @@ static CONFIGS: LazyLock<HashMap<&'static str, HighlightConfiguration>> = LazyLo
             "",
             ""
         ),
+        language!(
+            "java",
+            tree_sitter_java::LANGUAGE,
+            tree_sitter_java::HIGHLIGHTS_QUERY,
+            "",
+            "",
+        ),
         language!(
             "javascript",
             tree_sitter_javascript::LANGUAGE,
@@ -134,6 +155,13 @@ static CONFIGS: LazyLock<HashMap<&'static str, HighlightConfiguration>> = LazyLo
             tree_sitter_javascript::INJECTIONS_QUERY,
             tree_sitter_javascript::LOCALS_QUERY,
         ),
+        language!(
+            "lua",
+            tree_sitter_lua::LANGUAGE,
+            tree_sitter_lua::HIGHLIGHTS_QUERY,
+            tree_sitter_lua::INJECTIONS_QUERY,
+            tree_sitter_lua::LOCALS_QUERY,
+        ),
         language!(
             "markdown",
             tree_sitter_md::LANGUAGE,
@@ -155,6 +183,13 @@ static CONFIGS: LazyLock<HashMap<&'static str, HighlightConfiguration>> = LazyLo
             "",
             "",
         ),
+        language!(
+            "ocaml",
+            tree_sitter_ocaml::LANGUAGE_OCAML,
+            tree_sitter_ocaml::HIGHLIGHTS_QUERY,
+            "",
+            tree_sitter_ocaml::LOCALS_QUERY,
+        ),
         language!(
             "python",
             tree_sitter_python::LANGUAGE,
@@ -176,6 +211,20 @@ static CONFIGS: LazyLock<HashMap<&'static str, HighlightConfiguration>> = LazyLo
             tree_sitter_rust::INJECTIONS_QUERY,
             "",
         ),
+        language!(
+            "scheme",
+            tree_sitter_scheme::LANGUAGE,
+            tree_sitter_scheme::HIGHLIGHTS_QUERY,
+            "",
+            "",
+        ),
+        language!(
+            "sql",
+            tree_sitter_sequel::LANGUAGE,
+            tree_sitter_sequel::HIGHLIGHTS_QUERY,
+            "",
+            "",
+        ),
         language!(
             "toml",
             tree_sitter_toml_ng::LANGUAGE,
@@ -212,6 +261,20 @@ static CONFIGS: LazyLock<HashMap<&'static str, HighlightConfiguration>> = LazyLo
                 tree_sitter_typescript::LOCALS_QUERY,
             ],
         ),
+        language!(
+            "yaml",
+            tree_sitter_yaml::LANGUAGE,
+            tree_sitter_yaml::HIGHLIGHTS_QUERY,
+            "",
+            "",
+        ),
+        language!(
+            "zig",
+            tree_sitter_zig::LANGUAGE,
+            tree_sitter_zig::HIGHLIGHTS_QUERY,
+            tree_sitter_zig::INJECTIONS_QUERY,
+            "",
+        ),
     ]
     .into_iter()
     .flatten()
@@ -230,6 +293,11 @@ fn expand_extension(ext: &str) -> &str {
 		"ts"  => "typescript",
 		"scss" => "css",
 		"glsl" => "c",
+		"golang" => "go",
+		"ml"  => "ocaml",
+		"yml" => "yaml",
+		"docker" => "dockerfile",
+		"containerfile" => "dockerfile",
 		other => other,
     }
 }
@@ -237,3 +305,13 @@ fn expand_extension(ext: &str) -> &str {
 pub fn get_config(name: &str) -> Option<&'static HighlightConfiguration> {
     CONFIGS.get(expand_extension(name))
 }
+
+/// Languages which are rendered as plain text on purpose.
+const PLAIN: &[&str] = &["", "text", "txt", "plain", "plaintext"];
+
+/// Whether a code block in the language would be left without highlighting
+/// by accident, that is the language is neither configured nor plain text.
+pub fn is_missing(name: &str) -> bool {
+    let name = name.strip_prefix("diff-").unwrap_or(name);
+    !PLAIN.contains(&name) && get_config(name).is_none()
+}
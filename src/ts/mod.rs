@@ -1,12 +1,19 @@
 mod captures;
-mod configs;
+pub(crate) mod configs;
+mod theme;
 
 use std::borrow::Cow;
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+use std::sync::LazyLock;
 
 use hern_doc::{Annotation, SnippetAnalysis, TextRange};
 use hypertext::{Raw, prelude::*};
+use regex::Regex;
 use tree_sitter_highlight::{HighlightEvent, Highlighter};
 
+pub use theme::generate_theme;
+
 pub enum TSEvent {
     Write {
         start: usize,
@@ -17,26 +24,207 @@ pub enum TSEvent {
     Close,
 }
 
-pub fn highlight(lang: &str, code: &str) -> impl Renderable {
-    highlight_with_analysis(lang, code, None)
+/// Attributes from the rest of a code fence info string, e.g.
+/// ```` ```rust title="main.rs" {3,5-7} linenos ````.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ListingOptions {
+    pub title: Option<String>,
+    /// 1-based ranges of the lines to highlight, kept as ranges so that a
+    /// typo like `{1-99999999}` costs nothing.
+    pub highlight: Vec<RangeInclusive<usize>>,
+    pub linenos: bool,
+}
+
+static RE_LISTING_ATTR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"title="([^"]*)"|title=(\S+)|\{([\d,\s-]*)\}|(\S+)"#)
+        .expect("Invalid listing attribute Regex")
+});
+
+impl ListingOptions {
+    pub fn parse(meta: &str) -> Self {
+        let mut options = Self::default();
+
+        for caps in RE_LISTING_ATTR.captures_iter(meta) {
+            if let Some(title) = caps.get(1).or_else(|| caps.get(2)) {
+                options.title = Some(title.as_str().to_string());
+            } else if let Some(ranges) = caps.get(3) {
+                for range in ranges.as_str().split(',').map(str::trim) {
+                    let (lo, hi) = range.split_once('-').unwrap_or((range, range));
+                    if let (Ok(lo), Ok(hi)) = (lo.trim().parse::<usize>(), hi.trim().parse()) {
+                        options.highlight.push(lo..=hi);
+                    }
+                }
+            } else if &caps[0] == "linenos" {
+                options.linenos = true;
+            }
+        }
+
+        options
+    }
+
+    /// Whether the listing has to be split into individually wrapped lines.
+    fn needs_lines(&self) -> bool {
+        self.linenos || !self.highlight.is_empty()
+    }
+
+    fn is_highlighted(&self, line: usize) -> bool {
+        self.highlight.iter().any(|range| range.contains(&line))
+    }
 }
 
 pub fn highlight_with_analysis(
     lang: &str,
     code: &str,
     analysis: Option<&SnippetAnalysis>,
+    options: ListingOptions,
 ) -> impl Renderable {
+    let html = if let Some(inner) = lang.strip_prefix("diff-") {
+        let (code, markers) = strip_diff_markers(code);
+        render_lines(to_lines(inner, &code, None), &options, &markers)
+    } else if options.needs_lines() {
+        render_lines(to_lines(lang, code, analysis), &options, &[])
+    } else {
+        to_html(lang, code, analysis)
+    };
+
     maud!(
-        figure .listing.syntax data-lang=(lang) {
+        figure .listing.syntax."listing--linenos"[options.linenos] data-lang=(lang) {
+            @if let Some(title) = &options.title {
+                figcaption .listing__title { (title) }
+            }
             pre {
                 code {
-                    (Raw::dangerously_create(to_html(lang, code, analysis)))
+                    (Raw::dangerously_create(&html))
                 }
             }
         }
     )
 }
 
+/// Highlights a short snippet of inline code, using the same capture classes
+/// as block listings.
+pub fn highlight_inline(lang: &str, code: &str) -> String {
+    maud!(
+        code .syntax data-lang=(lang) {
+            (Raw::dangerously_create(to_html(lang, code, None)))
+        }
+    )
+    .render()
+    .into_inner()
+}
+
+/// Wraps every line in its own element, so that it can be numbered and
+/// highlighted. Lines of a diff additionally carry their marker.
+fn render_lines(lines: Vec<String>, options: &ListingOptions, markers: &[char]) -> String {
+    let mut html = String::new();
+
+    for (i, line) in lines.into_iter().enumerate() {
+        let mut class = String::from("line");
+        if options.is_highlighted(i + 1) {
+            class.push_str(" line--highlight");
+        }
+
+        let marker = markers.get(i).copied();
+        match marker {
+            Some('+') => class.push_str(" diff-plus"),
+            Some('-') => class.push_str(" diff-minus"),
+            _ => (),
+        }
+
+        write!(html, "<span class=\"{class}\" data-line=\"{}\">", i + 1).unwrap();
+        if let Some(marker) = marker {
+            write!(html, "<span class=\"diff-marker\">{marker}</span>").unwrap();
+        }
+        write!(html, "{line}\n</span>").unwrap();
+    }
+
+    html
+}
+
+/// Splits the `+`, `-` and ` ` markers off the lines of a diff, so that the
+/// remaining code can be highlighted as a whole.
+fn strip_diff_markers(diff: &str) -> (String, Vec<char>) {
+    let mut code = String::with_capacity(diff.len());
+    let mut markers = vec![];
+
+    for line in diff.split_inclusive('\n') {
+        // blank context lines often lose their leading space
+        let marker = line
+            .chars()
+            .next()
+            .filter(|c| matches!(c, '+' | '-' | ' '))
+            .unwrap_or(' ');
+        markers.push(marker);
+        code.push_str(line.strip_prefix(['+', '-', ' ']).unwrap_or(line));
+    }
+
+    (code, markers)
+}
+
+/// Renders the highlighted source split into lines. Highlight spans which
+/// cross a line boundary are closed at the end of the line and reopened at
+/// the start of the next one, so that every line is balanced HTML.
+fn to_lines(lang: &str, code: &str, analysis: Option<&SnippetAnalysis>) -> Vec<String> {
+    let annotations = analysis
+        .map(|analysis| analysis.annotations.as_slice())
+        .unwrap_or(&[]);
+
+    let mut lines = vec![];
+    let mut line = String::new();
+    let mut open = Vec::<String>::new();
+
+    for event in get_events(lang, code) {
+        match event {
+            TSEvent::Write { start, end, .. } => {
+                let mut cursor = start;
+
+                while cursor < end {
+                    let (stop, newline) = match code[cursor..end].find('\n') {
+                        Some(offset) => (cursor + offset, true),
+                        None => (end, false),
+                    };
+
+                    line.push_str(&write_source_with_annotations(
+                        code,
+                        cursor,
+                        stop,
+                        &code[cursor..stop],
+                        annotations,
+                    ));
+
+                    if newline {
+                        line.push_str(&"</span>".repeat(open.len()));
+                        lines.push(std::mem::take(&mut line));
+                        line.extend(open.iter().map(String::as_str));
+                        cursor = stop + 1;
+                    } else {
+                        cursor = stop;
+                    }
+                }
+            }
+            TSEvent::Enter(class) => {
+                let tag = format!(
+                    "<span class=\"{}\">",
+                    crate::utils::escape_html_attr(&class.replace('.', "-"))
+                );
+                line.push_str(&tag);
+                open.push(tag);
+            }
+            TSEvent::Close => {
+                line.push_str("</span>");
+                open.pop();
+            }
+        }
+    }
+
+    // a trailing newline doesn't start another line
+    if !code.is_empty() && !code.ends_with('\n') {
+        lines.push(line);
+    }
+
+    lines
+}
+
 fn to_html(lang: &str, code: &str, analysis: Option<&SnippetAnalysis>) -> String {
     let annotations = analysis
         .map(|analysis| analysis.annotations.as_slice())
@@ -221,3 +409,54 @@ fn hover_placement_attr(code: &str, trigger: TextRange) -> &'static str {
 fn ranges_overlap(lhs: TextRange, rhs: TextRange) -> bool {
     lhs.start < rhs.end && rhs.start < lhs.end
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ListingOptions;
+
+    #[test]
+    fn listing_options_are_parsed_from_the_info_string() {
+        let options = ListingOptions::parse(r#"title="src/main.rs" {3,5-7} linenos"#);
+
+        assert_eq!(options.title.as_deref(), Some("src/main.rs"));
+        assert!(options.linenos);
+
+        let lines = (1..=8).filter(|&line| options.is_highlighted(line));
+        assert_eq!(lines.collect::<Vec<_>>(), [3, 5, 6, 7]);
+
+        let options = ListingOptions::parse("{2-99999999999}");
+        assert!(options.is_highlighted(1_000_000));
+    }
+
+    #[test]
+    fn diff_markers_are_split_off_and_reapplied() {
+        let (code, markers) = super::strip_diff_markers("-let x = 1;\n+let x = 2;\n x\n");
+
+        assert_eq!(code, "let x = 1;\nlet x = 2;\nx\n");
+        assert_eq!(markers, ['-', '+', ' ']);
+
+        let html = super::render_lines(
+            super::to_lines("rust", &code, None),
+            &ListingOptions::default(),
+            &markers,
+        );
+        assert!(html.starts_with("<span class=\"line diff-minus\" data-line=\"1\">"));
+        assert!(html.contains("<span class=\"line diff-plus\" data-line=\"2\">"));
+        assert!(html.contains("<span class=\"keyword"));
+    }
+
+    #[test]
+    fn lines_stay_balanced_when_spans_cross_them() {
+        let code = "/* one\ntwo */\nlet x = 1;\n";
+        let lines = super::to_lines("rust", code, None);
+
+        assert_eq!(lines.len(), 3);
+        for line in &lines {
+            assert_eq!(
+                line.matches("<span").count(),
+                line.matches("</span>").count()
+            );
+        }
+        assert!(lines[1].starts_with("<span class=\"comment"));
+    }
+}
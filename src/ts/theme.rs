@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+
+use camino::Utf8Path;
+use hauchiwa::error::RuntimeError;
+use serde::Deserialize;
+
+use crate::ts::captures;
+
+/// Syntax themes for both color schemes, as read from the theme file.
+#[derive(Deserialize)]
+struct Themes {
+    light: Theme,
+    dark: Theme,
+}
+
+#[derive(Deserialize)]
+struct Theme {
+    /// Prefix of the CSS custom properties holding the palette.
+    prefix: String,
+    palette: BTreeMap<String, String>,
+    background: String,
+    foreground: String,
+    border: String,
+    /// Colors by capture name. A capture without its own entry falls back to
+    /// the entry of its closest dotted prefix, e.g. `keyword.return` to
+    /// `keyword`.
+    captures: BTreeMap<String, String>,
+}
+
+impl Theme {
+    /// Palette entries become CSS variables, anything else is used verbatim.
+    fn color(&self, color: &str) -> String {
+        if self.palette.contains_key(color) {
+            format!("var(--{}-{color})", self.prefix)
+        } else {
+            color.to_string()
+        }
+    }
+
+    fn resolve(&self, capture: &str) -> Option<&str> {
+        let mut name = capture;
+        loop {
+            if let Some(color) = self.captures.get(name) {
+                return Some(color);
+            }
+            name = &name[..name.rfind('.')?];
+        }
+    }
+
+    /// Theme entries which don't match any capture, most likely typos.
+    fn unmatched(&self) -> Vec<&str> {
+        self.captures
+            .keys()
+            .filter(|key| {
+                !captures::NAMES.iter().any(|name| {
+                    name.strip_prefix(key.as_str())
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+                })
+            })
+            .map(String::as_str)
+            .collect()
+    }
+
+    fn to_scss(&self, selector: &str) -> String {
+        let mut out = String::new();
+
+        writeln!(out, "// Generated from the syntax theme file, do not edit.").unwrap();
+        writeln!(out, ":root {{").unwrap();
+        for (name, value) in &self.palette {
+            writeln!(out, "  --{}-{name}: {value};", self.prefix).unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+
+        writeln!(out, "{selector} {{").unwrap();
+        writeln!(out, "  background-color: {};", self.color(&self.background)).unwrap();
+        writeln!(out, "  border-color: {};", self.color(&self.border)).unwrap();
+        writeln!(out, "  color: {};\n", self.color(&self.foreground)).unwrap();
+        writeln!(out, "  &::before {{").unwrap();
+        writeln!(out, "    background: {};", self.color(&self.border)).unwrap();
+        writeln!(out, "  }}\n").unwrap();
+
+        for name in captures::NAMES {
+            if let Some(color) = self.resolve(name) {
+                let class = name.replace('.', "-");
+                writeln!(out, "  .{class} {{ color: {}; }}", self.color(color)).unwrap();
+            }
+        }
+
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
+
+/// Generates the `_light.scss` and `_dark.scss` syntax partials in `out_dir`
+/// from the theme file at `path`.
+pub fn generate_theme(path: &Utf8Path, out_dir: &Utf8Path) -> Result<(), RuntimeError> {
+    let text = fs::read_to_string(path)?;
+    let themes: Themes = serde_json::from_str(&text).map_err(|err| {
+        RuntimeError::msg(format!("Failed to parse syntax theme '{path}': {err}"))
+    })?;
+
+    for (name, theme, selector) in [
+        ("light", &themes.light, ".syntax"),
+        ("dark", &themes.dark, "html.dark .syntax"),
+    ] {
+        for key in theme.unmatched() {
+            hauchiwa::tracing::warn!("{name} syntax theme entry '{key}' matches no capture");
+        }
+
+        let scss = theme.to_scss(selector);
+        let out = out_dir.join(format!("_{name}.scss"));
+
+        // rewriting an unchanged file would trigger the stylesheet watcher
+        if fs::read_to_string(&out).ok().as_deref() != Some(scss.as_str()) {
+            fs::write(&out, scss)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Theme;
+
+    #[test]
+    fn captures_fall_back_to_their_dotted_prefix() {
+        let theme: Theme = serde_json::from_str(
+            r##"{
+                "prefix": "t",
+                "palette": { "blue": "#00f" },
+                "background": "#fff",
+                "foreground": "#000",
+                "border": "#ccc",
+                "captures": { "keyword": "blue", "keyword.return": "red", "keywords": "red" }
+            }"##,
+        )
+        .unwrap();
+
+        assert_eq!(theme.resolve("keyword.function"), Some("blue"));
+        assert_eq!(theme.resolve("keyword.return"), Some("red"));
+        assert_eq!(theme.resolve("comment"), None);
+        assert_eq!(theme.unmatched(), ["keywords"]);
+
+        let scss = theme.to_scss(".syntax");
+        assert!(scss.contains("  .keyword-conditional-ternary { color: var(--t-blue); }"));
+        assert!(scss.contains("  .keyword-return { color: red; }"));
+    }
+}
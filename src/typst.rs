@@ -1,17 +1,284 @@
 use std::{
+    collections::BTreeMap,
+    fs,
     io::Write,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::SystemTime,
 };
 
+use blake2::{Blake2b, Digest, digest::consts::U32};
+use data_encoding::HEXLOWER;
 use hauchiwa::error::RuntimeError;
-use sequoia_openpgp::anyhow;
+use regex::Regex;
+
+/// Inline snippets are sized to their content instead of a full page.
+const PREAMBLE: &str = "#set page(width: auto, height: auto, margin: 0.5em)\n";
+
+/// Where compiler outputs are kept between builds.
+const CACHE_DIR: &str = ".cache/typst";
+
+/// Once the cache grows past this many bytes, the least recently used
+/// entries are evicted.
+const CACHE_LIMIT: u64 = 512 * 1024 * 1024;
+
+static CACHE_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Version string of the installed compiler, part of every cache key so that
+/// upgrading `typst` invalidates old outputs.
+static VERSION: LazyLock<Option<String>> = LazyLock::new(|| {
+    let output = Command::new("typst").arg("--version").output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+});
+
+/// Disables the on-disk cache, so every document is compiled from scratch.
+pub fn disable_cache() {
+    CACHE_ENABLED.store(false, Ordering::Relaxed);
+}
 
 pub fn render_typst(code: &str) -> Result<String, RuntimeError> {
+    let source = format!("{PREAMBLE}{code}");
+    let output = run_typst(&["c", "--format=svg", "-", "-"], source.as_bytes())?;
+
+    Ok(String::from_utf8(output)?)
+}
+
+/// Compiles a document to SVG, one `<svg>` per page. Typst can only write a
+/// single page to stdout, so the pages go through a temporary directory.
+pub fn render_svg_pages(source: &[u8]) -> Result<String, RuntimeError> {
+    let html = cached(&["svg-pages"], source, || {
+        render_svg_pages_uncached(source).map(String::into_bytes)
+    })?;
+
+    Ok(String::from_utf8(html)?)
+}
+
+fn render_svg_pages_uncached(source: &[u8]) -> Result<String, RuntimeError> {
+    // a fresh directory per run, so that neither stale pages of an earlier
+    // render nor a concurrent render of the same document can leak in
+    let dir = tempfile::Builder::new()
+        .prefix("kamoshi-typst-")
+        .tempdir()?;
+
+    let pattern = dir.path().join("{0p}.svg");
+    let pattern = pattern
+        .to_str()
+        .ok_or_else(|| RuntimeError::msg("temporary directory is not valid UTF-8"))?;
+
+    spawn_typst(&["c", "--format=svg", "-", pattern], source)?;
+
+    let mut paths = fs::read_dir(dir.path())?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "svg"));
+
+    // zero-padded page numbers sort in page order
+    paths.sort();
+
+    let mut html = String::new();
+    for path in paths {
+        let svg = fs::read_to_string(path)?;
+        html.push_str(&format!("<div class=\"typst-page\">{svg}</div>"));
+    }
+
+    Ok(html)
+}
+
+/// Compiles a document with Typst's experimental HTML export, returning the
+/// contents of `<body>`.
+pub fn render_html_body(source: &[u8]) -> Result<String, RuntimeError> {
+    let args = ["c", "--features=html", "--format=html", "-", "-"];
+    let html = String::from_utf8(run_typst(&args, source)?)?;
+
+    let body = html
+        .split_once("<body>")
+        .and_then(|(_, rest)| rest.rsplit_once("</body>"))
+        .map_or(html.as_str(), |(body, _)| body);
+
+    Ok(body.to_string())
+}
+
+/// Runs the `typst` CLI with `input` piped through stdin, returning stdout.
+/// On failure the error carries the compiler diagnostics from stderr.
+/// Successful outputs are cached on disk.
+pub fn run_typst(args: &[&str], input: &[u8]) -> Result<Vec<u8>, RuntimeError> {
+    cached(args, input, || spawn_typst(args, input))
+}
+
+/// Looks up the output for `args` and `input` in the cache, computing and
+/// storing it on a miss.
+fn cached(
+    args: &[&str],
+    input: &[u8],
+    compute: impl FnOnce() -> Result<Vec<u8>, RuntimeError>,
+) -> Result<Vec<u8>, RuntimeError> {
+    let Some(version) = VERSION.as_deref() else {
+        return compute();
+    };
+
+    if !CACHE_ENABLED.load(Ordering::Relaxed) {
+        return compute();
+    }
+
+    // documents read from stdin resolve their paths against the working
+    // directory, which typst takes as the project root
+    let Some(key) = cache_key(version, args, input, Path::new(".")) else {
+        return compute();
+    };
+
+    let path = PathBuf::from(CACHE_DIR).join(key);
+
+    if let Ok(data) = fs::read(&path) {
+        // bump the entry so that it's evicted last
+        if let Ok(file) = fs::File::options().append(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+
+        return Ok(data);
+    }
+
+    let data = compute()?;
+
+    if let Err(err) = store(&path, &data) {
+        hauchiwa::tracing::warn!("failed to cache typst output: {err}");
+    }
+
+    Ok(data)
+}
+
+/// Digest of everything the output depends on: the compiler version, the
+/// arguments, the document and the contents of the files it reads. `None`
+/// when the document reads a file whose path is only known at compile time.
+fn cache_key(version: &str, args: &[&str], input: &[u8], root: &Path) -> Option<String> {
+    let mut files = BTreeMap::new();
+    collect_files(input, root, root, &mut files)?;
+
+    let mut hasher = Blake2b::<U32>::new();
+    // length prefixes keep the boundaries between the parts unambiguous
+    for part in std::iter::once(version.as_bytes())
+        .chain(args.iter().map(|arg| arg.as_bytes()))
+        .chain(std::iter::once(input))
+        .chain(
+            files
+                .iter()
+                .flat_map(|(path, data)| [path.as_os_str().as_encoded_bytes(), data.as_slice()]),
+        )
+    {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+
+    Some(HEXLOWER.encode(&hasher.finalize()))
+}
+
+/// Matches the ways a document can read a file, capturing the path when it's
+/// a string literal.
+static RE_FILE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?:#(?:import|include)\s+|\b(?:image|read|json|csv|yaml|toml|xml|cbor|bibliography|plugin)\(\s*)(?:"([^"]*)")?"#,
+    )
+    .expect("Invalid regex")
+});
+
+/// Reads the files referenced by `source` into `files`, following imports of
+/// other Typst files. Absolute paths are relative to `root`, others to `dir`.
+fn collect_files(
+    source: &[u8],
+    dir: &Path,
+    root: &Path,
+    files: &mut BTreeMap<PathBuf, Vec<u8>>,
+) -> Option<()> {
+    let source = String::from_utf8_lossy(source);
+
+    for caps in RE_FILE.captures_iter(&source) {
+        let path = caps.get(1)?.as_str();
+
+        // packages are versioned, so they never change under the same name
+        if path.starts_with('@') {
+            continue;
+        }
+
+        let path = match path.strip_prefix('/') {
+            Some(path) => root.join(path),
+            None => dir.join(path),
+        };
+
+        if files.contains_key(&path) {
+            continue;
+        }
+
+        // a missing file fails the compilation, which is never cached
+        let data = fs::read(&path).unwrap_or_default();
+        files.insert(path.clone(), data.clone());
+
+        if path.extension().is_some_and(|ext| ext == "typ") {
+            collect_files(&data, path.parent().unwrap_or(root), root, files)?;
+        }
+    }
+
+    Some(())
+}
+
+fn store(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    static EVICTION: Mutex<()> = Mutex::new(());
+
+    fs::create_dir_all(CACHE_DIR)?;
+
+    // write to a unique file first, so that concurrent readers never observe
+    // a partially written entry
+    let temp = path.with_extension(format!(
+        "tmp{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&temp, data)?;
+    fs::rename(&temp, path)?;
+
+    let _guard = EVICTION.lock();
+
+    let mut entries = fs::read_dir(CACHE_DIR)?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            // skip files which are still being written
+            if entry.path().extension().is_some() {
+                return None;
+            }
+            let meta = entry.metadata().ok()?;
+            Some((meta.modified().ok()?, meta.len(), entry.path()))
+        })
+        .collect::<Vec<_>>();
+
+    let mut total = entries.iter().map(|(_, size, _)| size).sum::<u64>();
+    if total <= CACHE_LIMIT {
+        return Ok(());
+    }
+
+    // oldest first
+    entries.sort_unstable();
+
+    for (_, size, path) in entries {
+        if total <= CACHE_LIMIT {
+            break;
+        }
+        fs::remove_file(path)?;
+        total -= size;
+    }
+
+    Ok(())
+}
+
+fn spawn_typst(args: &[&str], input: &[u8]) -> Result<Vec<u8>, RuntimeError> {
     let mut child = Command::new("typst")
-        .arg("c")
-        .arg("--format=svg")
-        .arg("-")
-        .arg("-")
+        .args(args)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -21,8 +288,8 @@ pub fn render_typst(code: &str) -> Result<String, RuntimeError> {
         let stdin = child
             .stdin
             .as_mut()
-            .ok_or(anyhow::anyhow!("stdin not piped"))?;
-        stdin.write_all(code.as_bytes())?;
+            .ok_or_else(|| RuntimeError::msg("typst stdin was not piped"))?;
+        stdin.write_all(input)?;
         stdin.flush()?;
     }
 
@@ -30,8 +297,38 @@ pub fn render_typst(code: &str) -> Result<String, RuntimeError> {
 
     if !output.status.success() {
         let stderr = String::from_utf8(output.stderr)?;
-        Err(anyhow::anyhow!("Typst SSR failed:\n{stderr}"))?
+        return Err(RuntimeError::msg(stderr));
     }
 
-    Ok(String::from_utf8(output.stdout)?)
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    #[test]
+    fn cache_key_covers_included_files() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+
+        fs::create_dir(root.join("lib")).unwrap();
+        fs::write(root.join("lib/theme.typ"), "#let accent = blue").unwrap();
+        fs::write(root.join("lib/util.typ"), r#"#import "theme.typ": accent"#).unwrap();
+
+        let input = br#"#import "/lib/util.typ": *
+#import "@preview/cetz:0.3.4"
+#image("logo.png")"#;
+        let key = |input: &[u8]| super::cache_key("typst 0.13", &["c"], input, root);
+
+        let before = key(input).unwrap();
+        assert_eq!(key(input).unwrap(), before);
+
+        // a change two imports deep has to miss the cache
+        fs::write(root.join("lib/theme.typ"), "#let accent = red").unwrap();
+        assert_ne!(key(input).unwrap(), before);
+
+        // paths computed at compile time can't be tracked
+        assert_eq!(key(br#"#image(sys.inputs.logo)"#), None);
+    }
 }